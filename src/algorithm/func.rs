@@ -1,8 +1,12 @@
 pub use crate::ut::data::Signal;
 
-/// Step function
-/// TODO: rename
-pub fn activation_step(z: f32) -> f32 {
+/// Slope of the negative part of leaky ReLU
+pub const LEAKY_RELU_ALPHA: f32 = 0.01;
+/// Saturation value of ELU for large negative arguments, taken w/ the opposite sign
+pub const ELU_ALPHA: f32 = 1.0;
+
+/// Rectified linear unit, max(0, z)
+pub fn activation_relu(z: f32) -> f32 {
     if z < 0.0 {
         0.0
     } else {
@@ -11,8 +15,7 @@ pub fn activation_step(z: f32) -> f32 {
 }
 
 /// da/dz
-/// TODO: rename
-pub fn activation_step_d(z: f32) -> f32 {
+pub fn activation_relu_d(z: f32) -> f32 {
     if z < 0.0 {
         0.0
     } else {
@@ -20,20 +23,115 @@ pub fn activation_step_d(z: f32) -> f32 {
     }
 }
 
-/// Mean squared error fucntion's derivative
-///
-/// `reference` - desired output, training value
-/// `value` - factual output
-/// TODO: SSE, not MSE.
-pub fn cost_mse_d(reference: f32, value: f32) -> f32 {
-    -2.0f32 * reference + 2.0f32 * value
+/// Logistic function, 1 / (1 + e^-z)
+pub fn activation_sigmoid(z: f32) -> f32 {
+    1.0 / (1.0 + (-z).exp())
+}
+
+pub fn activation_sigmoid_d(z: f32) -> f32 {
+    let a = activation_sigmoid(z);
+    a * (1.0 - a)
+}
+
+pub fn activation_tanh(z: f32) -> f32 {
+    z.tanh()
+}
+
+pub fn activation_tanh_d(z: f32) -> f32 {
+    let a = z.tanh();
+    1.0 - a * a
+}
+
+/// ReLU w/ a small slope for negative arguments, so the gradient never vanishes
+pub fn activation_leaky_relu(z: f32) -> f32 {
+    if z < 0.0 {
+        LEAKY_RELU_ALPHA * z
+    } else {
+        z
+    }
+}
+
+pub fn activation_leaky_relu_d(z: f32) -> f32 {
+    if z < 0.0 {
+        LEAKY_RELU_ALPHA
+    } else {
+        1.0
+    }
+}
+
+/// Exponential linear unit
+pub fn activation_elu(z: f32) -> f32 {
+    if z < 0.0 {
+        ELU_ALPHA * (z.exp() - 1.0)
+    } else {
+        z
+    }
+}
+
+pub fn activation_elu_d(z: f32) -> f32 {
+    if z < 0.0 {
+        ELU_ALPHA * z.exp()
+    } else {
+        1.0
+    }
+}
+
+/// sqrt(2 / pi)
+const GELU_K: f32 = 0.797_884_6;
+const GELU_C: f32 = 0.044_715;
+
+/// Gaussian error linear unit, tanh approximation
+pub fn activation_gelu(z: f32) -> f32 {
+    0.5 * z * (1.0 + (GELU_K * (z + GELU_C * z * z * z)).tanh())
+}
+
+pub fn activation_gelu_d(z: f32) -> f32 {
+    let t = (GELU_K * (z + GELU_C * z * z * z)).tanh();
+    let dt = (1.0 - t * t) * GELU_K * (1.0 + 3.0 * GELU_C * z * z);
+
+    0.5 * (1.0 + t) + 0.5 * z * dt
+}
+
+/// ln(1 + e^z), a smooth approximation of ReLU
+pub fn activation_softplus(z: f32) -> f32 {
+    // Avoids overflow of e^z for large arguments
+    z.max(0.0) + (-z.abs()).exp().ln_1p()
 }
 
-pub fn sum_squared_errors_vector_cost_function(reference: &Signal, value: &Signal) -> f32 {
-    assert!(reference.len() == value.len());
-    reference.iter()
-        .zip(value.iter())
-        .fold(0.0f32, |accumulated, (a, b)| {
-            accumulated + (a + b).powf(2.0)
-        })
+pub fn activation_softplus_d(z: f32) -> f32 {
+    activation_sigmoid(z)
+}
+
+/// z * sigmoid(z)
+pub fn activation_swish(z: f32) -> f32 {
+    z * activation_sigmoid(z)
+}
+
+pub fn activation_swish_d(z: f32) -> f32 {
+    let s = activation_sigmoid(z);
+    s + z * s * (1.0 - s)
+}
+
+/// a = z, e.g. for regression outputs
+pub fn activation_identity(z: f32) -> f32 {
+    z
+}
+
+pub fn activation_identity_d(_z: f32) -> f32 {
+    1.0
+}
+
+/// Vector-wise activation, e^z_i / sum_j(e^z_j). The maximum is subtracted
+/// from the weighted sums beforehand, so the exponent cannot overflow.
+pub fn activation_softmax(z: &[f32], a: &mut [f32]) {
+    assert!(z.len() == a.len());
+    let z_max = z.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let mut sum = 0.0f32;
+
+    for (a, z) in a.iter_mut().zip(z) {
+        *a = (z - z_max).exp();
+        sum += *a;
+    }
+
+    a.iter_mut().for_each(|a| *a /= sum);
 }
@@ -0,0 +1,221 @@
+//! Numerical verification of back propagation.
+//!
+//! Each weight and bias is perturbed by `±epsilon`, and the central finite
+//! difference of the loss is compared against the analytic derivative
+//! computed by `BackPropagation`.
+
+use super::{BackPropagation, ForwardPropagation, Signal, loss::Loss, optimizer::Parameter};
+use crate::network::Network;
+use crate::error::{Error, Result};
+
+/// Lower bound for the denominator of the relative error, so derivatives
+/// close to 0 are compared by their absolute difference
+const MAGNITUDE_MIN: f32 = 1e-3;
+
+/// Relative error of two derivatives, `|a - n| / max(|a|, |n|)`
+pub fn relative_error(analytic: f32, numeric: f32) -> f32 {
+    (analytic - numeric).abs() / analytic.abs().max(numeric.abs()).max(MAGNITUDE_MIN)
+}
+
+/// Derivative of the loss by a single parameter, computed both ways
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Mismatch {
+    /// Index of the parameter within the group, row-major for weights
+    pub index: usize,
+    pub analytic: f32,
+    pub numeric: f32,
+    pub relative_error: f32,
+}
+
+/// The worst mismatch within a group of parameters
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GroupReport {
+    pub parameter: Parameter,
+    pub worst: Mismatch,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct GradientCheckReport {
+    /// Weights and biases of every layer but the input one, in that order
+    pub groups: Vec<GroupReport>,
+}
+
+impl GradientCheckReport {
+    /// The largest relative error over all the parameters, NaN if any is NaN
+    pub fn max_relative_error(&self) -> f32 {
+        self.groups.iter()
+            .map(|group| group.worst.relative_error)
+            .fold(0.0f32, |max, error| if is_worse(error, max) { error } else { max })
+    }
+
+    /// Whether every parameter's relative error is within `tolerance`
+    pub fn is_within(&self, tolerance: f32) -> bool {
+        self.max_relative_error() <= tolerance
+    }
+}
+
+/// Compares back propagation's gradient against finite differences of the
+/// loss for a single sample. The network is left intact.
+///
+/// Activations w/ kinks (ReLU, MAE, ...) may produce spurious mismatches, if
+/// a weighed sum is within `epsilon` of a kink.
+pub fn gradient_check(net: &Network, input: &Signal, reference: &Signal, loss: &dyn Loss, epsilon: f32)
+    -> Result<GradientCheckReport>
+{
+    if net.n_layers() < 2 {
+        return Err(Error::InvalidGeometry(net.geometry()));
+    }
+
+    let mut net = net.clone();
+    ForwardPropagation.try_run(&mut net, input)?;
+    let mut back_propagation = BackPropagation::from_network(&net);
+    back_propagation.try_accumulate(&net, reference, loss)?;
+    let gradient = back_propagation.gradient();
+    let mut probe = net.clone();
+    let mut groups = Vec::with_capacity(2 * (net.n_layers() - 1));
+
+    for ilayer in 1..net.n_layers() {
+        for parameter in [Parameter::Weights(ilayer), Parameter::Biases(ilayer)] {
+            let analytic = parameter_slice(gradient, parameter);
+            let mut worst: Option<Mismatch> = None;
+
+            for (index, analytic) in analytic.iter().enumerate() {
+                let numeric = numeric_derivative(&mut probe, parameter, index, input, reference, loss,
+                    epsilon);
+                let mismatch = Mismatch{
+                    index,
+                    analytic: *analytic,
+                    numeric,
+                    relative_error: relative_error(*analytic, numeric),
+                };
+
+                if worst.is_none_or(|worst| is_worse(mismatch.relative_error, worst.relative_error)) {
+                    worst = Some(mismatch);
+                }
+            }
+
+            if let Some(worst) = worst {
+                groups.push(GroupReport{parameter, worst});
+            }
+        }
+    }
+
+    Ok(GradientCheckReport{groups})
+}
+
+/// NaN is the worst of all
+fn is_worse(error: f32, than: f32) -> bool {
+    error.is_nan() || error > than
+}
+
+fn parameter_slice(net: &Network, parameter: Parameter) -> &[f32] {
+    match parameter {
+        Parameter::Weights(ilayer) => net.w_matrix(ilayer).as_slice(),
+        Parameter::Biases(ilayer) => net.b_vec(ilayer),
+    }
+}
+
+fn parameter_slice_mut(net: &mut Network, parameter: Parameter) -> &mut [f32] {
+    match parameter {
+        Parameter::Weights(ilayer) => net.w_matrix_mut(ilayer).as_mut_slice(),
+        Parameter::Biases(ilayer) => net.b_vec_mut(ilayer),
+    }
+}
+
+/// Central finite difference of the loss by a parameter. The parameter is
+/// restored afterwards.
+fn numeric_derivative(net: &mut Network, parameter: Parameter, index: usize, input: &Signal,
+    reference: &Signal, loss: &dyn Loss, epsilon: f32) -> f32
+{
+    let original = parameter_slice(net, parameter)[index];
+    let loss_at = |value: f32, net: &mut Network| {
+        parameter_slice_mut(net, parameter)[index] = value;
+        ForwardPropagation.run(net, input);
+
+        loss.value(reference, net.output_layer())
+    };
+    let plus = loss_at(original + epsilon, net);
+    let minus = loss_at(original - epsilon, net);
+    parameter_slice_mut(net, parameter)[index] = original;
+
+    (plus - minus) / (2.0 * epsilon)
+}
+
+#[cfg(test)]
+mod test_gradcheck {
+    use super::*;
+    use crate::algorithm::{ActivationFunctionFamily, init, loss};
+    use crate::ut;
+
+    const EPSILON: f32 = 1e-2;
+    const TOLERANCE: f32 = 2e-2;
+
+    fn check(hidden: ActivationFunctionFamily, output: ActivationFunctionFamily, loss: &dyn Loss,
+        reference: &Signal) -> GradientCheckReport
+    {
+        let mut rng = ut::rng_from_seed(1, ut::RngStream::Initialization);
+        let mut net = Network::from_geometry(&vec![3, 5, 4, reference.len()]);
+        init::network_init(&mut net, init::WeightInit::XavierUniform, init::BiasInit::Uniform(-0.1, 0.1),
+            &mut rng);
+        net.set_activation(1, hidden);
+        net.set_activation(2, hidden);
+        net.set_activation(3, output);
+
+        gradient_check(&net, &vec![0.3, -0.7, 0.9], reference, loss, EPSILON).unwrap()
+    }
+
+    #[test]
+    fn activations() {
+        let reference = vec![0.2f32, 0.5, 0.3];
+
+        for activation in ActivationFunctionFamily::ALL {
+            let report = check(activation, ActivationFunctionFamily::Sigmoid, &loss::Sse, &reference);
+            assert!(report.is_within(TOLERANCE), "hidden {:?}: {:?}", activation, report);
+            let report = check(ActivationFunctionFamily::Tanh, activation, &loss::Sse, &reference);
+            assert!(report.is_within(TOLERANCE), "output {:?}: {:?}", activation, report);
+        }
+    }
+
+    #[test]
+    fn losses() {
+        let one_hot = vec![0.0f32, 1.0, 0.0];
+        let losses: Vec<(&str, Box<dyn Loss>, ActivationFunctionFamily)> = vec![
+            ("mse", Box::new(loss::Mse), ActivationFunctionFamily::Identity),
+            ("sse", Box::new(loss::Sse), ActivationFunctionFamily::Identity),
+            ("mae", Box::new(loss::Mae), ActivationFunctionFamily::Sigmoid),
+            ("huber", Box::new(loss::Huber{delta: 0.25}), ActivationFunctionFamily::Sigmoid),
+            ("bce", Box::new(loss::BinaryCrossEntropy), ActivationFunctionFamily::Sigmoid),
+            ("cce", Box::new(loss::CategoricalCrossEntropy), ActivationFunctionFamily::Softmax),
+            ("cce unfused", Box::new(loss::CategoricalCrossEntropy), ActivationFunctionFamily::Sigmoid),
+            ("hinge", Box::new(loss::Hinge), ActivationFunctionFamily::Sigmoid),
+            ("kl", Box::new(loss::KlDivergence), ActivationFunctionFamily::Softmax),
+        ];
+
+        for (name, loss, output) in losses {
+            let report = check(ActivationFunctionFamily::Tanh, output, loss.as_ref(), &one_hot);
+            assert_eq!(report.groups.len(), 6);
+            assert!(report.is_within(TOLERANCE), "{}: {:?}", name, report);
+        }
+    }
+
+    /// A deliberately broken gradient must be reported
+    #[test]
+    fn detects_mismatch() {
+        struct Broken;
+
+        impl Loss for Broken {
+            fn value(&self, reference: &Signal, output: &Signal) -> f32 {
+                loss::Sse.value(reference, output)
+            }
+
+            fn gradient(&self, reference: &Signal, output: &Signal, gradient: &mut Signal) {
+                loss::Sse.gradient(reference, output, gradient);
+                gradient[0] *= -1.0;
+            }
+        }
+
+        let report = check(ActivationFunctionFamily::Tanh, ActivationFunctionFamily::Identity, &Broken,
+            &vec![1.0, 0.0, 0.0]);
+        assert!(!report.is_within(TOLERANCE));
+    }
+}
@@ -0,0 +1,180 @@
+//! Weight and bias initialization schemes.
+//!
+//! Fan-in and fan-out of a layer's weights are the lengths of the previous
+//! and the current layer, as given by `Network::geometry()`.
+
+use crate::network::{Network, Matrix};
+use rand::Rng;
+use std::f32::consts::PI;
+
+/// Strategy for initializing the weights of a layer
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WeightInit {
+    /// U(from, to), regardless of the fan
+    Uniform(f32, f32),
+    /// Each weight is set to the value
+    Constant(f32),
+    /// Glorot & Bengio, U(-l, l), l = sqrt(6 / (fan_in + fan_out))
+    XavierUniform,
+    /// Glorot & Bengio, N(0, 2 / (fan_in + fan_out))
+    XavierNormal,
+    /// He et al., suited for ReLU-like activations, U(-l, l), l = sqrt(6 / fan_in)
+    HeUniform,
+    /// He et al., N(0, 2 / fan_in)
+    HeNormal,
+    /// LeCun, suited for tanh and sigmoid, U(-l, l), l = sqrt(3 / fan_in)
+    LecunUniform,
+    /// LeCun, N(0, 1 / fan_in)
+    LecunNormal,
+    /// Saxe et al., a (semi-)orthogonal matrix scaled by the gain
+    Orthogonal(f32),
+}
+
+/// Strategy for initializing the biases of a layer
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BiasInit {
+    Zeros,
+    Constant(f32),
+    /// U(from, to)
+    Uniform(f32, f32),
+}
+
+/// Sample of the standard normal distribution (Box-Muller transform)
+pub fn sample_standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f32 {
+    // Keeps `ln` away from 0
+    let u1: f32 = 1.0 - rng.gen::<f32>();
+    let u2: f32 = rng.gen::<f32>();
+
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+/// Initializes weights and biases of every layer but the input one.
+pub fn network_init<R: Rng + ?Sized>(net: &mut Network, weight_init: WeightInit, bias_init: BiasInit,
+    rng: &mut R)
+{
+    for ilayer in 1..net.n_layers() {
+        layer_init(net, ilayer, weight_init, bias_init, rng);
+    }
+}
+
+/// Initializes weights of the edges ending on a layer, and biases of its nodes.
+///
+/// Pre: `ilayer` > 0
+pub fn layer_init<R: Rng + ?Sized>(net: &mut Network, ilayer: usize, weight_init: WeightInit,
+    bias_init: BiasInit, rng: &mut R)
+{
+    assert!(ilayer > 0);
+    weights_init(net.w_matrix_mut(ilayer), weight_init, rng);
+
+    for b in net.b_vec_mut(ilayer).iter_mut() {
+        *b = match bias_init {
+            BiasInit::Zeros => 0.0f32,
+            BiasInit::Constant(val) => val,
+            BiasInit::Uniform(from, to) => rng.gen_range(from..to),
+        };
+    }
+}
+
+/// Initializes a weight matrix, rows being the inputs, and columns the outputs
+pub fn weights_init<R: Rng + ?Sized>(w: &mut Matrix, weight_init: WeightInit, rng: &mut R) {
+    let fan_in = w.n_rows() as f32;
+    let fan_out = w.n_cols() as f32;
+    let uniform = |w: &mut Matrix, limit: f32, rng: &mut R| {
+        w.as_mut_slice().iter_mut().for_each(|x| *x = rng.gen_range(-limit..=limit));
+    };
+    let normal = |w: &mut Matrix, std: f32, rng: &mut R| {
+        w.as_mut_slice().iter_mut().for_each(|x| *x = std * sample_standard_normal(rng));
+    };
+
+    match weight_init {
+        WeightInit::Uniform(from, to) => w.as_mut_slice().iter_mut().for_each(|x| *x = rng.gen_range(from..to)),
+        WeightInit::Constant(val) => w.fill(val),
+        WeightInit::XavierUniform => uniform(w, (6.0 / (fan_in + fan_out)).sqrt(), rng),
+        WeightInit::XavierNormal => normal(w, (2.0 / (fan_in + fan_out)).sqrt(), rng),
+        WeightInit::HeUniform => uniform(w, (6.0 / fan_in).sqrt(), rng),
+        WeightInit::HeNormal => normal(w, (2.0 / fan_in).sqrt(), rng),
+        WeightInit::LecunUniform => uniform(w, (3.0 / fan_in).sqrt(), rng),
+        WeightInit::LecunNormal => normal(w, (1.0 / fan_in).sqrt(), rng),
+        WeightInit::Orthogonal(gain) => orthogonal_init(w, gain, rng),
+    }
+}
+
+/// Fills a matrix w/ orthonormal rows, or orthonormal columns, whichever are
+/// fewer, by Gram-Schmidt orthogonalization of a Gaussian matrix.
+fn orthogonal_init<R: Rng + ?Sized>(w: &mut Matrix, gain: f32, rng: &mut R) {
+    let (n_rows, n_cols) = (w.n_rows(), w.n_cols());
+    let transpose = n_rows > n_cols;
+    // Vectors to orthonormalize, and their length
+    let (n_vectors, len) = if transpose { (n_cols, n_rows) } else { (n_rows, n_cols) };
+    let mut vectors: Vec<Vec<f32>> = Vec::with_capacity(n_vectors);
+
+    while vectors.len() < n_vectors {
+        let mut v: Vec<f32> = (0..len).map(|_| sample_standard_normal(rng)).collect();
+
+        for u in &vectors {
+            let projection: f32 = v.iter().zip(u).map(|(a, b)| a * b).sum();
+            v.iter_mut().zip(u).for_each(|(a, b)| *a -= projection * b);
+        }
+
+        let norm = v.iter().map(|a| a * a).sum::<f32>().sqrt();
+
+        // A degenerate draw, retry
+        if norm < 1e-6 {
+            continue;
+        }
+
+        v.iter_mut().for_each(|a| *a /= norm);
+        vectors.push(v);
+    }
+
+    for (i, v) in vectors.iter().enumerate() {
+        for (j, val) in v.iter().enumerate() {
+            if transpose {
+                w.set(j, i, gain * val);
+            } else {
+                w.set(i, j, gain * val);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_init {
+    use super::*;
+    use crate::ut;
+
+    #[test]
+    fn he_normal_variance() {
+        let mut rng = ut::rng_from_seed(0, ut::RngStream::Initialization);
+        let mut net = Network::from_geometry(&vec![200, 300]);
+        network_init(&mut net, WeightInit::HeNormal, BiasInit::Zeros, &mut rng);
+        let w = net.w_matrix(1).as_slice();
+        let mean = w.iter().sum::<f32>() / w.len() as f32;
+        let variance = w.iter().map(|x| (x - mean) * (x - mean)).sum::<f32>() / w.len() as f32;
+
+        assert!(mean.abs() < 1e-2, "{}", mean);
+        assert!((variance - 2.0 / 200.0).abs() < 1e-3, "{}", variance);
+        assert!(net.b_vec(1).iter().all(|b| *b == 0.0));
+    }
+
+    #[test]
+    fn orthogonal() {
+        let mut rng = ut::rng_from_seed(0, ut::RngStream::Initialization);
+
+        for (n_rows, n_cols) in [(3, 5), (5, 3), (4, 4)] {
+            let mut w = Matrix::new(n_rows, n_cols, 0.0f32);
+            weights_init(&mut w, WeightInit::Orthogonal(1.0), &mut rng);
+            // Gram matrix over the fewer of rows and columns must be identity
+            let n = n_rows.min(n_cols);
+            let get = |i: usize, k: usize| if n_rows <= n_cols { w.get(i, k) } else { w.get(k, i) };
+
+            for i in 0..n {
+                for j in 0..n {
+                    let dot: f32 = (0..n_rows.max(n_cols)).map(|k| get(i, k) * get(j, k)).sum();
+                    let expected = if i == j { 1.0 } else { 0.0 };
+                    assert!((dot - expected).abs() < 1e-4, "{}x{} [{}, {}]: {}", n_rows, n_cols, i, j, dot);
+                }
+            }
+        }
+    }
+}
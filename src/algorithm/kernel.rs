@@ -0,0 +1,200 @@
+//! Dense vector kernels used by forward and back propagation.
+//!
+//! The instruction set is chosen at runtime: AVX2 w/ FMA on x86_64 CPUs which
+//! support them, portable code otherwise. Fused multiply-add rounds once per
+//! operation, so results may differ between the two in the last bits.
+
+use crate::network::Matrix;
+use std::sync::OnceLock;
+
+/// Instruction set the kernels are run with
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Isa {
+    Portable,
+    Avx2Fma,
+}
+
+/// Detects the best instruction set on the first call
+pub fn isa() -> Isa {
+    static ISA: OnceLock<Isa> = OnceLock::new();
+
+    *ISA.get_or_init(|| {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+                return Isa::Avx2Fma;
+            }
+        }
+
+        Isa::Portable
+    })
+}
+
+/// Sum of `x[i] * y[i]`
+///
+/// Pre: `x` and `y` are of the same length
+#[inline]
+pub fn dot(x: &[f32], y: &[f32]) -> f32 {
+    assert!(x.len() == y.len());
+
+    match isa() {
+        #[cfg(target_arch = "x86_64")]
+        // Safe: the CPU supports the instructions, lengths are checked
+        Isa::Avx2Fma => unsafe { avx2::dot(x, y) },
+        _ => portable::dot(x, y),
+    }
+}
+
+/// `y += alpha * x`
+///
+/// Pre: `x` and `y` are of the same length
+#[inline]
+pub fn axpy(alpha: f32, x: &[f32], y: &mut [f32]) {
+    assert!(x.len() == y.len());
+
+    match isa() {
+        #[cfg(target_arch = "x86_64")]
+        // Safe: the CPU supports the instructions, lengths are checked
+        Isa::Avx2Fma => unsafe { avx2::axpy(alpha, x, y) },
+        _ => portable::axpy(alpha, x, y),
+    }
+}
+
+/// `x *= alpha`
+#[inline]
+pub fn scale(alpha: f32, x: &mut [f32]) {
+    x.iter_mut().for_each(|x| *x *= alpha);
+}
+
+/// Outer product accumulation, `m[i][j] += x[i] * y[j]`
+///
+/// Pre: `m` is `x.len()` by `y.len()`
+pub fn outer_add(x: &[f32], y: &[f32], m: &mut Matrix) {
+    assert!(m.n_rows() == x.len() && m.n_cols() == y.len());
+
+    for (i, x) in x.iter().enumerate() {
+        axpy(*x, y, m.row_mut(i));
+    }
+}
+
+mod portable {
+    /// Number of independent accumulators, lets the compiler vectorize the sum
+    const N_LANES: usize = 8;
+
+    pub fn dot(x: &[f32], y: &[f32]) -> f32 {
+        let mut lanes = [0.0f32; N_LANES];
+        let x_chunks = x.chunks_exact(N_LANES);
+        let y_chunks = y.chunks_exact(N_LANES);
+        let tail: f32 = x_chunks.remainder().iter().zip(y_chunks.remainder()).map(|(x, y)| x * y).sum();
+
+        for (x, y) in x_chunks.zip(y_chunks) {
+            for i in 0..N_LANES {
+                lanes[i] += x[i] * y[i];
+            }
+        }
+
+        lanes.iter().sum::<f32>() + tail
+    }
+
+    pub fn axpy(alpha: f32, x: &[f32], y: &mut [f32]) {
+        for (y, x) in y.iter_mut().zip(x) {
+            *y += alpha * x;
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    const N_LANES: usize = 8;
+
+    /// Pre: AVX2 and FMA are supported, `x.len() == y.len()`
+    #[target_feature(enable = "avx2,fma")]
+    pub unsafe fn dot(x: &[f32], y: &[f32]) -> f32 {
+        let n = x.len() - x.len() % N_LANES;
+        let mut acc = _mm256_setzero_ps();
+
+        for i in (0..n).step_by(N_LANES) {
+            let xv = _mm256_loadu_ps(x.as_ptr().add(i));
+            let yv = _mm256_loadu_ps(y.as_ptr().add(i));
+            acc = _mm256_fmadd_ps(xv, yv, acc);
+        }
+
+        // Horizontal sum of the 8 lanes
+        let sum4 = _mm_add_ps(_mm256_castps256_ps128(acc), _mm256_extractf128_ps(acc, 1));
+        let sum2 = _mm_add_ps(sum4, _mm_movehl_ps(sum4, sum4));
+        let sum1 = _mm_add_ss(sum2, _mm_shuffle_ps(sum2, sum2, 1));
+
+        _mm_cvtss_f32(sum1) + x[n..].iter().zip(&y[n..]).map(|(x, y)| x * y).sum::<f32>()
+    }
+
+    /// Pre: AVX2 and FMA are supported, `x.len() == y.len()`
+    #[target_feature(enable = "avx2,fma")]
+    pub unsafe fn axpy(alpha: f32, x: &[f32], y: &mut [f32]) {
+        let n = x.len() - x.len() % N_LANES;
+        let alpha_v = _mm256_set1_ps(alpha);
+
+        for i in (0..n).step_by(N_LANES) {
+            let xv = _mm256_loadu_ps(x.as_ptr().add(i));
+            let yv = _mm256_loadu_ps(y.as_ptr().add(i));
+            _mm256_storeu_ps(y.as_mut_ptr().add(i), _mm256_fmadd_ps(alpha_v, xv, yv));
+        }
+
+        for (y, x) in y[n..].iter_mut().zip(&x[n..]) {
+            *y = alpha.mul_add(*x, *y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_kernel {
+    use super::*;
+
+    fn vectors(len: usize) -> (Vec<f32>, Vec<f32>) {
+        let x = (0..len).map(|i| (i as f32 * 0.37).sin()).collect();
+        let y = (0..len).map(|i| (i as f32 * 0.11).cos()).collect();
+
+        (x, y)
+    }
+
+    /// Lengths cover empty vectors, and ones w/ and w/o a tail past full lanes
+    #[test]
+    fn against_naive() {
+        for len in [0, 1, 7, 8, 9, 16, 31, 100] {
+            let (x, y) = vectors(len);
+            let naive_dot: f32 = x.iter().zip(&y).map(|(x, y)| x * y).sum();
+            let mut naive_axpy = y.clone();
+            naive_axpy.iter_mut().zip(&x).for_each(|(y, x)| *y += 0.5 * x);
+            let mut results = vec![(portable::dot(&x, &y), {
+                let mut y = y.clone();
+                portable::axpy(0.5, &x, &mut y);
+                y
+            })];
+
+            #[cfg(target_arch = "x86_64")]
+            if isa() == Isa::Avx2Fma {
+                let mut y_avx = y.clone();
+                unsafe {
+                    avx2::axpy(0.5, &x, &mut y_avx);
+                    results.push((avx2::dot(&x, &y), y_avx));
+                }
+            }
+
+            for (dot, axpy) in results {
+                assert!((dot - naive_dot).abs() < 1e-4, "{}: {} vs {}", len, dot, naive_dot);
+
+                for (a, b) in axpy.iter().zip(&naive_axpy) {
+                    assert!((a - b).abs() < 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn outer_product() {
+        let mut m = Matrix::new(2, 3, 1.0);
+        outer_add(&[1.0, 2.0], &[1.0, 0.0, -1.0], &mut m);
+        assert_eq!(m.as_slice(), &[2.0, 1.0, 0.0, 3.0, 1.0, -1.0]);
+    }
+}
@@ -0,0 +1,270 @@
+//! Loss (cost) functions over whole output signals.
+
+pub use crate::ut::data::Signal;
+
+/// Lower bound for probabilities passed to logarithms and divisions
+const PROBABILITY_MIN: f32 = 1e-7;
+
+#[inline]
+fn clamp_probability(p: f32) -> f32 {
+    p.clamp(PROBABILITY_MIN, 1.0 - PROBABILITY_MIN)
+}
+
+/// Cost of a network's output given the reference (desired) one.
+///
+/// Pre: `reference`, `output`, and `gradient` are of the same length
+pub trait Loss: Sync {
+    /// Value of the cost
+    fn value(&self, reference: &Signal, output: &Signal) -> f32;
+
+    /// Partial derivatives of the cost by each of the outputs
+    fn gradient(&self, reference: &Signal, output: &Signal, gradient: &mut Signal);
+
+    /// Whether dC/dz over a softmax output layer reduces to
+    /// `output - reference`, so back propagation may skip the softmax
+    /// Jacobian.
+    fn is_softmax_fused(&self) -> bool {
+        false
+    }
+}
+
+/// Mean squared error, mean((o - r)^2)
+pub struct Mse;
+
+impl Loss for Mse {
+    fn value(&self, reference: &Signal, output: &Signal) -> f32 {
+        Sse.value(reference, output) / output.len() as f32
+    }
+
+    fn gradient(&self, reference: &Signal, output: &Signal, gradient: &mut Signal) {
+        let n = output.len() as f32;
+
+        for ((g, r), o) in gradient.iter_mut().zip(reference).zip(output) {
+            *g = 2.0 * (o - r) / n;
+        }
+    }
+}
+
+/// Sum of squared errors, sum((o - r)^2)
+pub struct Sse;
+
+impl Loss for Sse {
+    fn value(&self, reference: &Signal, output: &Signal) -> f32 {
+        assert!(reference.len() == output.len());
+        reference.iter().zip(output).map(|(r, o)| (o - r) * (o - r)).sum()
+    }
+
+    fn gradient(&self, reference: &Signal, output: &Signal, gradient: &mut Signal) {
+        for ((g, r), o) in gradient.iter_mut().zip(reference).zip(output) {
+            *g = 2.0 * (o - r);
+        }
+    }
+}
+
+/// Mean absolute error, mean(|o - r|)
+pub struct Mae;
+
+impl Loss for Mae {
+    fn value(&self, reference: &Signal, output: &Signal) -> f32 {
+        assert!(reference.len() == output.len());
+        reference.iter().zip(output).map(|(r, o)| (o - r).abs()).sum::<f32>() / output.len() as f32
+    }
+
+    fn gradient(&self, reference: &Signal, output: &Signal, gradient: &mut Signal) {
+        let n = output.len() as f32;
+
+        for ((g, r), o) in gradient.iter_mut().zip(reference).zip(output) {
+            *g = if o > r {
+                1.0 / n
+            } else if o < r {
+                -1.0 / n
+            } else {
+                0.0
+            };
+        }
+    }
+}
+
+/// Huber loss averaged over the outputs: quadratic for errors within `delta`,
+/// linear beyond that
+pub struct Huber {
+    pub delta: f32,
+}
+
+impl Loss for Huber {
+    fn value(&self, reference: &Signal, output: &Signal) -> f32 {
+        assert!(reference.len() == output.len());
+        let sum: f32 = reference.iter().zip(output)
+            .map(|(r, o)| {
+                let e = (o - r).abs();
+
+                if e <= self.delta {
+                    0.5 * e * e
+                } else {
+                    self.delta * (e - 0.5 * self.delta)
+                }
+            })
+            .sum();
+
+        sum / output.len() as f32
+    }
+
+    fn gradient(&self, reference: &Signal, output: &Signal, gradient: &mut Signal) {
+        let n = output.len() as f32;
+
+        for ((g, r), o) in gradient.iter_mut().zip(reference).zip(output) {
+            *g = (o - r).clamp(-self.delta, self.delta) / n;
+        }
+    }
+}
+
+/// Binary cross-entropy averaged over the outputs, each output being an
+/// independent probability: mean(-r * ln(o) - (1 - r) * ln(1 - o))
+pub struct BinaryCrossEntropy;
+
+impl Loss for BinaryCrossEntropy {
+    fn value(&self, reference: &Signal, output: &Signal) -> f32 {
+        assert!(reference.len() == output.len());
+        let sum: f32 = reference.iter().zip(output)
+            .map(|(r, o)| {
+                let o = clamp_probability(*o);
+                -r * o.ln() - (1.0 - r) * (1.0 - o).ln()
+            })
+            .sum();
+
+        sum / output.len() as f32
+    }
+
+    fn gradient(&self, reference: &Signal, output: &Signal, gradient: &mut Signal) {
+        let n = output.len() as f32;
+
+        for ((g, r), o) in gradient.iter_mut().zip(reference).zip(output) {
+            let o = clamp_probability(*o);
+            *g = (o - r) / (o * (1.0 - o)) / n;
+        }
+    }
+}
+
+/// Categorical cross-entropy, -sum(r * ln(o)). Meant for a softmax output
+/// layer and a reference summing up to 1, e.g. one-hot.
+pub struct CategoricalCrossEntropy;
+
+impl Loss for CategoricalCrossEntropy {
+    fn value(&self, reference: &Signal, output: &Signal) -> f32 {
+        assert!(reference.len() == output.len());
+        reference.iter().zip(output)
+            .map(|(r, o)| -r * o.max(PROBABILITY_MIN).ln())
+            .sum()
+    }
+
+    fn gradient(&self, reference: &Signal, output: &Signal, gradient: &mut Signal) {
+        for ((g, r), o) in gradient.iter_mut().zip(reference).zip(output) {
+            *g = -r / o.max(PROBABILITY_MIN);
+        }
+    }
+
+    fn is_softmax_fused(&self) -> bool {
+        true
+    }
+}
+
+/// Hinge loss averaged over the outputs, mean(max(0, 1 - t * o)). References
+/// are expected to be in {0, 1}, and are mapped onto targets t in {-1, 1}.
+pub struct Hinge;
+
+impl Loss for Hinge {
+    fn value(&self, reference: &Signal, output: &Signal) -> f32 {
+        assert!(reference.len() == output.len());
+        let sum: f32 = reference.iter().zip(output)
+            .map(|(r, o)| (1.0 - (2.0 * r - 1.0) * o).max(0.0))
+            .sum();
+
+        sum / output.len() as f32
+    }
+
+    fn gradient(&self, reference: &Signal, output: &Signal, gradient: &mut Signal) {
+        let n = output.len() as f32;
+
+        for ((g, r), o) in gradient.iter_mut().zip(reference).zip(output) {
+            let t = 2.0 * r - 1.0;
+            *g = if 1.0 - t * o > 0.0 { -t / n } else { 0.0 };
+        }
+    }
+}
+
+/// Kullback-Leibler divergence of the output distribution from the reference
+/// one, sum(r * ln(r / o)). Terms w/ r = 0 contribute nothing.
+pub struct KlDivergence;
+
+impl Loss for KlDivergence {
+    fn value(&self, reference: &Signal, output: &Signal) -> f32 {
+        assert!(reference.len() == output.len());
+        reference.iter().zip(output)
+            .filter(|(r, _)| **r > 0.0)
+            .map(|(r, o)| r * (r / o.max(PROBABILITY_MIN)).ln())
+            .sum()
+    }
+
+    fn gradient(&self, reference: &Signal, output: &Signal, gradient: &mut Signal) {
+        for ((g, r), o) in gradient.iter_mut().zip(reference).zip(output) {
+            *g = -r / o.max(PROBABILITY_MIN);
+        }
+    }
+
+    /// Differs from categorical cross-entropy by the reference's entropy,
+    /// which does not depend on the output
+    fn is_softmax_fused(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test_loss {
+    use super::*;
+
+    /// Compares gradients against central finite differences
+    #[test]
+    fn gradients() {
+        const H: f32 = 1e-3;
+        let losses: Vec<(&str, Box<dyn Loss>)> = vec![
+            ("mse", Box::new(Mse)),
+            ("sse", Box::new(Sse)),
+            ("mae", Box::new(Mae)),
+            ("huber", Box::new(Huber{delta: 0.25})),
+            ("bce", Box::new(BinaryCrossEntropy)),
+            ("cce", Box::new(CategoricalCrossEntropy)),
+            ("hinge", Box::new(Hinge)),
+            ("kl", Box::new(KlDivergence)),
+        ];
+        let reference = vec![0.0f32, 1.0, 0.0, 0.0];
+        let output = vec![0.2f32, 0.45, 0.3, 0.05];
+        let mut gradient = vec![0.0f32; output.len()];
+
+        for (name, loss) in losses {
+            loss.gradient(&reference, &output, &mut gradient);
+
+            for i in 0..output.len() {
+                let mut plus = output.clone();
+                let mut minus = output.clone();
+                plus[i] += H;
+                minus[i] -= H;
+                let numeric = (loss.value(&reference, &plus) - loss.value(&reference, &minus))
+                    / (2.0 * H);
+                assert!((numeric - gradient[i]).abs() < 1e-2 * (1.0 + numeric.abs()),
+                    "{}[{}]: {} vs {}", name, i, numeric, gradient[i]);
+            }
+        }
+    }
+
+    #[test]
+    fn values() {
+        let reference = vec![0.0f32, 1.0];
+        let output = vec![0.5f32, 0.5];
+
+        assert_eq!(Sse.value(&reference, &output), 0.5);
+        assert_eq!(Mse.value(&reference, &output), 0.25);
+        assert_eq!(Mae.value(&reference, &output), 0.5);
+        assert!((CategoricalCrossEntropy.value(&reference, &output) - 2.0f32.ln()).abs() < 1e-6);
+        assert!(KlDivergence.value(&reference, &reference).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,279 @@
+//! Classification quality metrics.
+//!
+//! A sample's class is the index of the largest value of a signal, the
+//! reference one for the expected class, and the network's output for the
+//! predicted one.
+
+use super::{Signal, loss::Loss, try_test_network_forward_propagation};
+use crate::network::Network;
+use crate::error::{Error, Result};
+use crate::ut::{self, data::Dataset};
+
+/// Counts of samples by expected (rows) and predicted (columns) class
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConfusionMatrix {
+    n_classes: usize,
+    counts: Vec<usize>,
+}
+
+impl ConfusionMatrix {
+    pub fn new(n_classes: usize) -> ConfusionMatrix {
+        ConfusionMatrix{n_classes, counts: vec![0; n_classes * n_classes]}
+    }
+
+    pub fn n_classes(&self) -> usize {
+        self.n_classes
+    }
+
+    pub fn add(&mut self, expected: usize, predicted: usize) {
+        assert!(expected < self.n_classes && predicted < self.n_classes);
+        self.counts[expected * self.n_classes + predicted] += 1;
+    }
+
+    pub fn count(&self, expected: usize, predicted: usize) -> usize {
+        self.counts[expected * self.n_classes + predicted]
+    }
+
+    pub fn n_samples(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// Number of correctly predicted samples
+    pub fn n_correct(&self) -> usize {
+        (0..self.n_classes).map(|class| self.count(class, class)).sum()
+    }
+
+    /// Number of samples of the class
+    pub fn n_expected(&self, class: usize) -> usize {
+        (0..self.n_classes).map(|predicted| self.count(class, predicted)).sum()
+    }
+
+    /// Number of samples predicted to be of the class
+    pub fn n_predicted(&self, class: usize) -> usize {
+        (0..self.n_classes).map(|expected| self.count(expected, class)).sum()
+    }
+}
+
+/// Precision, recall, and their harmonic mean
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Scores {
+    pub precision: f32,
+    pub recall: f32,
+    pub f1: f32,
+}
+
+impl Scores {
+    /// Ratios w/ a zero denominator are 0
+    fn from_counts(n_true_positive: usize, n_predicted: usize, n_expected: usize) -> Scores {
+        let ratio = |num: usize, den: usize| if den == 0 { 0.0f32 } else { num as f32 / den as f32 };
+        let precision = ratio(n_true_positive, n_predicted);
+        let recall = ratio(n_true_positive, n_expected);
+        let f1 = if precision + recall == 0.0 { 0.0 } else { 2.0 * precision * recall / (precision + recall) };
+
+        Scores{precision, recall, f1}
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ClassificationReport {
+    pub n_samples: usize,
+    pub accuracy: f32,
+    /// Scores of each class, and the number of its samples
+    pub classes: Vec<(Scores, usize)>,
+    /// Unweighed mean of the per-class scores
+    pub macro_average: Scores,
+    /// Scores over the counts pooled across the classes. Each sample has one
+    /// class, so all of them are equal to the accuracy.
+    pub micro_average: Scores,
+    /// Pairs of k and the share of samples whose expected class is among the
+    /// k top-scoring outputs
+    pub top_k_accuracy: Vec<(usize, f32)>,
+    pub confusion_matrix: ConfusionMatrix,
+}
+
+/// Accumulates classification results sample by sample
+#[derive(Clone, Debug)]
+pub struct MetricsAccumulator {
+    confusion_matrix: ConfusionMatrix,
+    /// Pairs of k and the number of top-k hits
+    top_k_hits: Vec<(usize, usize)>,
+}
+
+impl MetricsAccumulator {
+    /// `top_k`: values of k to report top-k accuracy for
+    pub fn new(n_classes: usize, top_k: &[usize]) -> MetricsAccumulator {
+        MetricsAccumulator{
+            confusion_matrix: ConfusionMatrix::new(n_classes),
+            top_k_hits: top_k.iter().map(|k| (*k, 0)).collect(),
+        }
+    }
+
+    /// Pre: both signals are of `n_classes` length
+    pub fn add(&mut self, expected: &Signal, output: &Signal) {
+        if let Err(e) = self.try_add(expected, output) {
+            panic!("Failed to add a sample: {}", e);
+        }
+    }
+
+    /// Same as `add`, but reports signals of wrong length instead of
+    /// panicking
+    pub fn try_add(&mut self, expected: &Signal, output: &Signal) -> Result<()> {
+        Error::check_len(self.confusion_matrix.n_classes(), expected.len())?;
+        Error::check_len(self.confusion_matrix.n_classes(), output.len())?;
+        let expected_class = ut::signal_find_max_index(expected);
+        self.confusion_matrix.add(expected_class, ut::signal_find_max_index(output));
+        // Ties are resolved in favor of the lower index, as `signal_find_max_index` does. A
+        // non-finite output of the expected class, e.g. of a diverged network, ranks after every class.
+        let target = output[expected_class];
+        let rank = if target.is_finite() {
+            output.iter().enumerate()
+                .filter(|(class, value)| **value > target || (**value == target && *class < expected_class))
+                .count()
+        } else {
+            output.len()
+        };
+
+        for (k, n_hits) in self.top_k_hits.iter_mut() {
+            if rank < *k {
+                *n_hits += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn confusion_matrix(&self) -> &ConfusionMatrix {
+        &self.confusion_matrix
+    }
+
+    pub fn report(&self) -> ClassificationReport {
+        let matrix = &self.confusion_matrix;
+        let n_samples = matrix.n_samples();
+        let share = |n: usize| if n_samples == 0 { 0.0f32 } else { n as f32 / n_samples as f32 };
+        let classes: Vec<(Scores, usize)> = (0..matrix.n_classes())
+            .map(|class| (
+                Scores::from_counts(matrix.count(class, class), matrix.n_predicted(class),
+                    matrix.n_expected(class)),
+                matrix.n_expected(class),
+            ))
+            .collect();
+        let n_classes = classes.len().max(1) as f32;
+        let macro_average = Scores{
+            precision: classes.iter().map(|(scores, _)| scores.precision).sum::<f32>() / n_classes,
+            recall: classes.iter().map(|(scores, _)| scores.recall).sum::<f32>() / n_classes,
+            f1: classes.iter().map(|(scores, _)| scores.f1).sum::<f32>() / n_classes,
+        };
+
+        ClassificationReport{
+            n_samples,
+            accuracy: share(matrix.n_correct()),
+            classes,
+            macro_average,
+            micro_average: Scores::from_counts(matrix.n_correct(), n_samples, n_samples),
+            top_k_accuracy: self.top_k_hits.iter().map(|(k, n_hits)| (*k, share(*n_hits))).collect(),
+            confusion_matrix: matrix.clone(),
+        }
+    }
+}
+
+/// Runs the network over the dataset, see `test_network_forward_propagation`.
+/// Returns the mean loss, and the classification metrics.
+pub fn evaluate_classification(net: &Network, loss: &dyn Loss, dataset: &impl Dataset, top_k: &[usize])
+    -> (f32, ClassificationReport)
+{
+    match try_evaluate_classification(net, loss, dataset, top_k) {
+        Ok(evaluation) => evaluation,
+        Err(e) => panic!("Failed to evaluate the network: {}", e),
+    }
+}
+
+/// Same as `evaluate_classification`, but reports an inconsistent network, or
+/// samples which do not fit it, instead of panicking
+pub fn try_evaluate_classification(net: &Network, loss: &dyn Loss, dataset: &impl Dataset, top_k: &[usize])
+    -> Result<(f32, ClassificationReport)>
+{
+    let mut accumulator = MetricsAccumulator::new(net.try_output_layer()?.len(), top_k);
+    // The signals are checked to fit the network before they reach the hook
+    let mean_loss = try_test_network_forward_propagation(net, loss, dataset,
+        |expected, output| accumulator.add(expected, output))?;
+
+    Ok((mean_loss, accumulator.report()))
+}
+
+#[cfg(test)]
+mod test_metrics {
+    use super::*;
+
+    fn one_hot(class: usize) -> Signal {
+        let mut signal = vec![0.0f32; 3];
+        signal[class] = 1.0;
+
+        signal
+    }
+
+    #[test]
+    fn report() {
+        let mut accumulator = MetricsAccumulator::new(3, &[1, 2]);
+        // (expected, output)
+        let samples = [
+            (0, vec![0.7f32, 0.2, 0.1]),
+            (0, vec![0.3, 0.6, 0.1]),
+            (1, vec![0.1, 0.8, 0.1]),
+            (1, vec![0.1, 0.8, 0.1]),
+            (2, vec![0.5, 0.1, 0.4]),
+        ];
+
+        for (expected, output) in &samples {
+            accumulator.add(&one_hot(*expected), output);
+        }
+
+        let report = accumulator.report();
+        assert_eq!(report.n_samples, 5);
+        assert_eq!(report.confusion_matrix.count(0, 1), 1);
+        assert_eq!(report.confusion_matrix.count(2, 0), 1);
+        assert!((report.accuracy - 0.6).abs() < 1e-6);
+        assert_eq!(report.classes[0], (Scores{precision: 0.5, recall: 0.5, f1: 0.5}, 2));
+        assert!((report.classes[1].0.precision - 2.0 / 3.0).abs() < 1e-6);
+        assert_eq!(report.classes[1].0.recall, 1.0);
+        assert_eq!(report.classes[2], (Scores::default(), 1));
+        assert!((report.macro_average.recall - 0.5).abs() < 1e-6);
+        assert!((report.micro_average.f1 - report.accuracy).abs() < 1e-6);
+        assert_eq!(report.top_k_accuracy[0], (1, report.accuracy));
+        assert_eq!(report.top_k_accuracy[1], (2, 1.0));
+    }
+
+    /// Top-1 accuracy must equal the accuracy, even w/ tied outputs
+    #[test]
+    fn ties() {
+        let mut accumulator = MetricsAccumulator::new(3, &[1, 2]);
+        accumulator.add(&one_hot(1), &vec![0.4, 0.4, 0.2]);
+        accumulator.add(&one_hot(0), &vec![0.4, 0.4, 0.2]);
+        accumulator.add(&one_hot(2), &vec![0.3, 0.3, 0.3]);
+
+        let report = accumulator.report();
+        assert!((report.accuracy - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(report.top_k_accuracy[0], (1, report.accuracy));
+        assert!((report.top_k_accuracy[1].1 - 2.0 / 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn shape_mismatch() {
+        let mut accumulator = MetricsAccumulator::new(3, &[1]);
+        assert!(matches!(accumulator.try_add(&one_hot(0), &vec![0.5, 0.5]),
+            Err(Error::ShapeMismatch{expected: 3, actual: 2})));
+        assert_eq!(accumulator.report().n_samples, 0);
+    }
+
+    /// NaN outputs of a diverged network must not count as hits
+    #[test]
+    fn nan_outputs() {
+        let mut accumulator = MetricsAccumulator::new(3, &[1, 3]);
+
+        for class in 0..3 {
+            accumulator.add(&one_hot(class), &vec![f32::NAN; 3]);
+        }
+
+        accumulator.add(&one_hot(2), &vec![0.1, 0.2, f32::NAN]);
+        assert_eq!(accumulator.report().top_k_accuracy, vec![(1, 0.0), (3, 0.0)]);
+    }
+}
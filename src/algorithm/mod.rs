@@ -1,18 +1,32 @@
 /// Neural network activation and training algorithms.
 
 pub mod func;
+pub mod optimizer;
+pub mod loss;
+pub mod init;
+pub mod kernel;
+pub mod gradcheck;
+pub mod metrics;
+pub mod schedule;
+pub mod regularization;
 
 use crate::{network, ut::{self, data}};
+use crate::error::{Error, Result};
 use std::{assert, vec::Vec};
 use rand::distributions::{Distribution, Uniform};
+use rand::seq::SliceRandom;
+use std::ops::ControlFlow;
+use std::sync::{Mutex, RwLock, mpsc};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
 use network::Network;
+use optimizer::{Optimizer, Parameter};
+use loss::Loss;
 pub use crate::ut::data::Signal;
 
-/// Randomly initializes weights and biases of a network.
-pub fn network_init_random(net: &mut network::Network) {
-    let mut rng = rand::thread_rng();
+/// Randomly initializes weights and biases of a network, drawing from U(0, 1).
+pub fn network_init_random<R: rand::Rng + ?Sized>(net: &mut network::Network, rng: &mut R) {
     let gen = Uniform::from(0.0f32..1.0f32);
-    network_init_with_generator(net, &mut || gen.sample(&mut rng));
+    network_init_with_generator(net, &mut || gen.sample(rng));
 }
 
 /// Initializes weights and biases of a network with a constant value
@@ -20,59 +34,218 @@ pub fn network_init_with_value(net: &mut network::Network, val: f32) {
     network_init_with_generator(net, &mut || val);
 }
 
-/// Initializes the network with a provided generator.
-fn network_init_with_generator(net: &mut network::Network, generator: &mut dyn FnMut() -> f32) {
+/// Initializes weights and biases of a network, drawing each value from the
+/// generator.
+pub fn network_init_with_generator(net: &mut network::Network, generator: &mut dyn FnMut() -> f32) {
     for ilayer in 1..net.n_layers() {
         for (ifrom, ito) in net.edge_index_iter(ilayer) {
             net.set_w(ilayer, ifrom, ito, generator());
-            net.set_b(ilayer, ifrom, ito, generator());
+        }
+
+        for inode in 0..net.layer_len(ilayer) {
+            net.set_b(ilayer, inode, generator());
         }
     }
 }
 
 #[cfg(test)]
 mod test_module_functions {
-    use crate::network::Network;
+    use crate::{network::Network, ut};
     use super::network_init_random;
 
     #[test]
     fn network_random_initialization() {
+        let mut rng = ut::rng_from_seed(0, ut::RngStream::Initialization);
         let geometry = vec![128, 16, 32, 4];
         let mut network = Network::from_geometry(&geometry);
-        network_init_random(&mut network);
+        network_init_random(&mut network, &mut rng);
+    }
+}
+
+/// Weighed sums of a layer, `z = b + a_prev * w`
+fn layer_propagate(a_prev: &[f32], w: &network::Matrix, b: &[f32], z: &mut [f32]) {
+    z.copy_from_slice(b);
+
+    // Row by row, so the weights are read in the order they are stored
+    for (ifrom, a) in a_prev.iter().enumerate() {
+        kernel::axpy(*a, w.row(ifrom), z);
+    }
+}
+
+/// Activations of a layer from its weighed sums
+fn layer_activate(activation: ActivationFunctionFamily, z: &[f32], a: &mut [f32]) {
+    if activation == ActivationFunctionFamily::Softmax {
+        func::activation_softmax(z, a);
+
+        return;
+    }
+
+    let activate = ActivationProfile::new(activation).activation_function;
+
+    for (a, z) in a.iter_mut().zip(z) {
+        *a = activate(*z);
+    }
+}
+
+/// Scratch buffers of a forward pass: weighed sums and activations of each
+/// layer but the input one. Keeping them apart from the network lets
+/// multiple threads run inference on one network, each w/ its own workspace.
+#[derive(Clone, Debug, Default)]
+pub struct Workspace {
+    z: Vec<Signal>,
+    a: Vec<Signal>,
+}
+
+impl Workspace {
+    pub fn from_network(net: &Network) -> Workspace {
+        let mut workspace = Workspace::default();
+        workspace.fit(net);
+
+        workspace
     }
+
+    /// Resizes the buffers for the network's geometry
+    fn fit(&mut self, net: &Network) {
+        let n_layers = net.n_layers().saturating_sub(1);
+        self.z.resize(n_layers, Signal::new());
+        self.a.resize(n_layers, Signal::new());
+
+        for (i, (z, a)) in self.z.iter_mut().zip(self.a.iter_mut()).enumerate() {
+            z.resize(net.layer_len(i + 1), 0.0f32);
+            a.resize(net.layer_len(i + 1), 0.0f32);
+        }
+    }
+
+    /// Activations of the output layer computed by the last pass
+    pub fn output(&self) -> &Signal {
+        self.a.last().expect("Workspace of a network w/o hidden or output layers")
+    }
+}
+
+/// Scratch buffers of a batched forward pass, one row per sample
+#[derive(Clone, Debug, Default)]
+pub struct BatchWorkspace {
+    z: Vec<network::Matrix>,
+    a: Vec<network::Matrix>,
 }
 
-pub struct ForwardPropagation {
-    activate: fn(f32) -> f32,
+impl BatchWorkspace {
+    /// Resizes the buffers for the network's geometry and the batch length
+    fn fit(&mut self, net: &Network, n_samples: usize) {
+        let n_layers = net.n_layers().saturating_sub(1);
+        self.z.resize(n_layers, network::Matrix::new(0, 0, 0.0f32));
+        self.a.resize(n_layers, network::Matrix::new(0, 0, 0.0f32));
+
+        for (i, (z, a)) in self.z.iter_mut().zip(self.a.iter_mut()).enumerate() {
+            z.reshape(n_samples, net.layer_len(i + 1));
+            a.reshape(n_samples, net.layer_len(i + 1));
+        }
+    }
+
+    /// Outputs computed by the last pass, one row per sample
+    pub fn output(&self) -> &network::Matrix {
+        self.a.last().expect("Workspace of a network w/o hidden or output layers")
+    }
 }
 
+/// Forward propagation. Each layer is activated w/ the function it is
+/// configured with in the network.
+pub struct ForwardPropagation;
+
 impl ForwardPropagation {
     /// Forward propagation between adjacent layers
     fn network_update_layer_propagate(&self, net: &mut network::Network, ilayer: usize) {
+        let (a, w, b, z) = net.layer_propagation_mut(ilayer);
+        layer_propagate(a, w, b, z);
+    }
+
+    /// Activation of "sum" nodes
+    fn network_update_layer_activate(&self, net: &mut network::Network, ilayer: usize) {
         assert!(ilayer > 0);
+        let activation = net.activation(ilayer);
+        let (z, a) = net.layer_activation_mut(ilayer);
+        layer_activate(activation, z, a);
+    }
+
+    /// Runs the network on the input leaving it intact. Weighed sums and
+    /// activations are stored in the workspace, which is resized if it was
+    /// made for a network of another geometry.
+    pub fn try_predict<'a>(&self, net: &Network, input: &[f32], workspace: &'a mut Workspace)
+        -> Result<&'a Signal>
+    {
+        if net.n_layers() < 2 || !net.is_consistent() {
+            return Err(Error::InvalidGeometry(net.geometry()));
+        }
+
+        Error::check_len(net.layer_len(0), input.len())?;
+
+        Ok(self.predict(net, input, workspace))
+    }
+
+    /// `try_predict` w/o the checks
+    fn predict<'a>(&self, net: &Network, input: &[f32], workspace: &'a mut Workspace) -> &'a Signal {
+        workspace.fit(net);
+
+        for ilayer in 1..net.n_layers() {
+            let (head, tail) = workspace.a.split_at_mut(ilayer - 1);
+            let a_prev = head.last().map_or(input, |a| a.as_slice());
+            let z = &mut workspace.z[ilayer - 1];
+            layer_propagate(a_prev, net.w_matrix(ilayer), net.b_vec(ilayer), z);
+            layer_activate(net.activation(ilayer), z, &mut tail[0]);
+        }
+
+        workspace.output()
+    }
 
-        for ito in 0..net.layer_len(ilayer) {
-            let mut sum = 0.0f32;
+    /// Batched `try_predict`. `inputs` holds one sample per row, and so does
+    /// the returned matrix of outputs. Each layer is computed for the whole
+    /// batch by a single matrix product.
+    pub fn try_predict_batch<'a>(&self, net: &Network, inputs: &network::Matrix,
+        workspace: &'a mut BatchWorkspace) -> Result<&'a network::Matrix>
+    {
+        if net.n_layers() < 2 || !net.is_consistent() {
+            return Err(Error::InvalidGeometry(net.geometry()));
+        }
+
+        Error::check_len(net.layer_len(0), inputs.n_cols())?;
+        workspace.fit(net, inputs.n_rows());
 
-            for ifrom in 0..net.layer_len(ilayer - 1) {
-                let (w, b) = net.edge_coef_wb(ilayer, ifrom, ito);
-                sum += net.a(ilayer - 1, ifrom) * w + b;
+        for ilayer in 1..net.n_layers() {
+            let (head, tail) = workspace.a.split_at_mut(ilayer - 1);
+            let a_prev = head.last().unwrap_or(inputs);
+            let z = &mut workspace.z[ilayer - 1];
+            let a = &mut tail[0];
+            let b = net.b_vec(ilayer);
+
+            for isample in 0..z.n_rows() {
+                z.row_mut(isample).copy_from_slice(b);
             }
 
-            net.set_z(ilayer, ito, sum);
+            a_prev.mul_add_into(net.w_matrix(ilayer), z);
+
+            for isample in 0..z.n_rows() {
+                layer_activate(net.activation(ilayer), z.row(isample), a.row_mut(isample));
+            }
         }
+
+        Ok(workspace.output())
     }
 
-    /// Activation of "sum" nodes
-    fn network_update_layer_activate(&self, net: &mut network::Network, ilayer: usize) {
-        assert!(ilayer > 0);
+    /// Same as `run`, but reports an inconsistent network or an input of
+    /// wrong length instead of panicking
+    pub fn try_run(&self, net: &mut network::Network, input: &[f32]) -> Result<()> {
+        if !net.is_consistent() {
+            return Err(Error::InvalidGeometry(net.geometry()));
+        }
 
-        for i in 0..net.layer_len(ilayer) {
-            let z = net.z(ilayer, i);
-            let a = (self.activate)(z);
-            net.set_a(ilayer, i, a);
+        net.try_init_input_layer(input)?;
+
+        for ilayer in 1..net.n_layers() {
+            self.network_update_layer_propagate(net, ilayer);
+            self.network_update_layer_activate(net, ilayer);
         }
+
+        Ok(())
     }
 
     pub fn run(&self, net: &mut network::Network, input: &Signal) {
@@ -91,258 +264,340 @@ mod test_forward_propagation {
         ForwardPropagation,
         network_init_random,
         Signal,
-        func::activation_step,
-        Uniform, Distribution
+        ActivationFunctionFamily,
+        Error,
+        Workspace,
+        BatchWorkspace,
     };
     use crate::{network, ut};
 
     #[test]
     fn run() {
+        let mut rng = ut::rng_from_seed(0, ut::RngStream::Initialization);
         let geometry = vec![2, 4, 2, 4];
         let mut network = network::Network::from_geometry(&geometry);
         // Initialize random input
         let mut signal = ut::signal_stub_from_network_input(&network);
-        ut::vec_init_random(&mut signal, 0.0f32, 1.0f32);
+        ut::vec_init_random(&mut signal, 0.0f32, 1.0f32, &mut rng);
 
-        network_init_random(&mut network);
-        let forward_propagation = ForwardPropagation {
-            activate: activation_step,
-        };
-        forward_propagation.run(&mut network, &signal);
+        network_init_random(&mut network, &mut rng);
         let ilayer = network.n_layers() - 1;
+        network.set_activation(ilayer, ActivationFunctionFamily::Tanh);
+        ForwardPropagation.run(&mut network, &signal);
         let layer_len = network.layer_len(ilayer);
 
         for inode in 0..layer_len {
             assert!(!network.a(ilayer, inode).is_nan());
+            // All the weights are positive, so are the weighted sums
+            assert!(network.a(ilayer, inode) > 0.0 && network.a(ilayer, inode) < 1.0);
+        }
+    }
+
+    #[test]
+    fn try_run() {
+        let mut network = network::Network::try_from_geometry(&[3, 2]).unwrap();
+        network_init_random(&mut network, &mut ut::rng_from_seed(0, ut::RngStream::Initialization));
+
+        assert!(matches!(ForwardPropagation.try_run(&mut network, &[0.5, 0.5]),
+            Err(Error::ShapeMismatch{expected: 3, actual: 2})));
+        assert!(ForwardPropagation.try_run(&mut network, &[0.5, 0.5, 0.5]).is_ok());
+        assert!(!network.try_output_layer().unwrap()[0].is_nan());
+    }
+
+    /// Inference through a workspace must match the in-place forward pass,
+    /// and be usable from several threads on one network
+    #[test]
+    fn predict() {
+        let mut network = network::Network::from_geometry(&vec![3, 5, 4, 2]);
+        network_init_random(&mut network, &mut ut::rng_from_seed(0, ut::RngStream::Initialization));
+        network.set_activation(3, ActivationFunctionFamily::Softmax);
+        let inputs: Vec<Signal> = (0..4).map(|i| vec![i as f32 * 0.1, 0.5, 1.0 - i as f32 * 0.2]).collect();
+        let expected: Vec<Signal> = inputs.iter()
+            .map(|input| {
+                ForwardPropagation.run(&mut network, input);
+                network.output_layer().clone()
+            })
+            .collect();
+        let network = &network;
+
+        std::thread::scope(|scope| {
+            for (input, expected) in inputs.iter().zip(&expected) {
+                scope.spawn(move || {
+                    let mut workspace = Workspace::from_network(network);
+                    assert_eq!(&network.predict(input, &mut workspace), expected);
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn predict_batch() {
+        let mut network = network::Network::from_geometry(&vec![3, 5, 2]);
+        network_init_random(&mut network, &mut ut::rng_from_seed(0, ut::RngStream::Initialization));
+        network.set_activation(2, ActivationFunctionFamily::Softmax);
+        let inputs = network::Matrix::from_vec(4, 3,
+            (0..12).map(|i| i as f32 / 12.0).collect()).unwrap();
+        let outputs = network.predict_batch(&inputs, &mut BatchWorkspace::default());
+        let mut workspace = Workspace::from_network(&network);
+        assert_eq!((outputs.n_rows(), outputs.n_cols()), (4, 2));
+
+        for (input, output) in inputs.rows().zip(outputs.rows()) {
+            let expected = network.predict(input, &mut workspace);
+            assert!(ut::vecf32_float_safe_is_eq(output, &expected));
         }
     }
 }
 
-/// Cost function derivative for the output layer
-/// arg. 1: desired output layer value
-/// arg. 2: factual output layer value
-pub type Dcdz = fn(f32, f32) -> f32;
 /// Derivative of activation function by the weighed sum
 /// arg. 1: weighed sum value
 pub type Dadz = fn(f32) -> f32;
 pub type ActivationFunction = fn(f32) -> f32;
 pub type ActivationFunctionDerivative = Dadz;
-pub type CostFunctionDerivative = Dcdz;
-pub type CostFunction = fn(f32, f32) -> f32;
-pub type VectorCostFunction = for <'a> fn(&'a Signal, &'a Signal) -> f32;
 
-struct BackPropagation {
-    /// Const function
-    dcdz_output: Dcdz,
-    /// Activation function
-    dadz: Dadz,
-    /// Intermediate results storage
-    net_cache: network::Network,
-    /// Learning rate
-    epsilon: f32,
+/// Gradient descent by means of back propagation.
+///
+/// Gradients are computed by a backward sweep over the layers: dC/dz of a
+/// layer (its delta) is derived from dC/da of the same layer, and dC/da of the
+/// previous layer is derived from the delta.
+pub struct BackPropagation {
+    /// Cost function gradient by the activations of the layer being swept
+    dcda: Signal,
+    /// Whether dC/dz of the output layer is `a - reference`, i.e. the loss
+    /// and the softmax output layer are differentiated together. This is both
+    /// cheaper and numerically safer than chaining e.g. -reference / a
+    /// through the softmax Jacobian.
+    is_output_fused: bool,
+    /// dC/dz of each layer for the last accumulated sample. The input layer's
+    /// one is empty.
+    deltas: Vec<Signal>,
+    /// Sums of dC/dw and dC/db over the samples accumulated since the last update
+    gradient: network::Network,
+    /// Number of samples accumulated in `gradient`
+    n_accumulated: usize,
 }
 
 impl BackPropagation {
-    // TODO dcda
-    // TODO dzda
-
-    pub fn from_network(net: &network::Network,
-            dcdz_output: Dcdz,
-            dadz: Dadz, epsilon: f32
-    ) -> BackPropagation {
-        let geometry: Vec<usize> = (0..net.n_layers())
-            .map(|i| net.layer_len(i)).collect();
+    pub fn from_network(net: &network::Network) -> BackPropagation {
+        let geometry = net.geometry();
+        let mut gradient = network::Network::from_geometry(&geometry);
+        gradient.fill_parameters(0.0f32);
+        let deltas = (0..geometry.len())
+            .map(|ilayer| if ilayer == 0 { Signal::new() } else { vec![0.0f32; geometry[ilayer]] })
+            .collect();
+
         BackPropagation {
-            dcdz_output,
-            dadz,
-            net_cache: network::Network::from_geometry(&geometry),
-            epsilon
+            dcda: ut::signal_stub_from_network_output(net),
+            is_output_fused: false,
+            deltas,
+            gradient,
+            n_accumulated: 0,
         }
     }
 
-    fn dzda(
-        &mut self,
-        ialayer: usize,
-        ia: usize,
-        iz: usize,
-        net: &Network,
-        reference: &Signal
-    ) -> f32 {
-        net.w(ialayer + 1, ia, iz)
-    }
+    /// Derives dC/dz of a layer from dC/da in `self.dcda`, and the layer's
+    /// weighed sums and activations
+    fn layer_delta(&mut self, net: &Network, ilayer: usize, z: &[f32], a: &[f32], reference: &Signal) {
+        let is_output = ilayer == net.n_layers() - 1;
+        let activation = net.activation(ilayer);
+        let delta = &mut self.deltas[ilayer];
+
+        match (is_output && self.is_output_fused, activation) {
+            (true, _) => {
+                for ((delta, a), r) in delta.iter_mut().zip(a).zip(reference) {
+                    *delta = a - r;
+                }
+            },
+            // dC/dz_i = a_i * (dC/da_i - sum_j(dC/da_j * a_j))
+            (_, ActivationFunctionFamily::Softmax) => {
+                let dcda_dot_a = kernel::dot(&self.dcda, a);
 
-    /// Returns partial derivative C by a
-    ///
-    /// `ialayer` - index of the layer on which `a` resides
-    /// `ia` - index of `a`
-    fn dcda(
-        &mut self,
-        ialayer: usize,
-        ia: usize,
-        net: &network::Network,
-        reference: &Signal
-    ) -> f32 {
-        let mut ret = self.net_cache.a(ialayer, ia);
-
-        if ret.is_nan() {
-            ret = 0.0;
-
-            for iz in 0..net.layer_len(ialayer + 1) {
-                let dcdz = self.dcdz(ialayer + 1, iz, net, reference);
-                let dzda = self.dzda(ialayer, ia, iz, net, reference);
-                ret += dcdz * dzda;
-            }
+                for ((delta, a), dcda) in delta.iter_mut().zip(a).zip(&self.dcda) {
+                    *delta = a * (dcda - dcda_dot_a);
+                }
+            },
+            _ => {
+                let dadz = ActivationProfile::new(activation).activation_function_derivative;
+
+                for ((delta, z), dcda) in delta.iter_mut().zip(z).zip(&self.dcda) {
+                    *delta = dcda * dadz(*z);
+                }
+            },
         }
+    }
 
-        self.net_cache.set_a(ialayer, ia, ret);
+    /// Derives dC/da of layer `ilayer - 1` from the delta of layer `ilayer`,
+    /// dz/da being the weights
+    fn layer_dcda_prev(&mut self, net: &Network, ilayer: usize) {
+        let w = net.w_matrix(ilayer);
+        let delta = &self.deltas[ilayer];
+        self.dcda.resize(net.layer_len(ilayer - 1), 0.0f32);
 
-        ret
+        for (ia, dcda) in self.dcda.iter_mut().enumerate() {
+            *dcda = kernel::dot(w.row(ia), delta);
+        }
     }
 
-    /// Returns partial derivative z by w
+    /// Calculates gradients of the cost for one sample, and adds them up to
+    /// the ones accumulated since the last update. The network is left intact.
+    /// `network`: the ANN instance
+    /// `reference` the reference (desired) output
+    /// `loss` the cost function
     ///
-    /// `ilayer` - layer of w
-    /// `ifrom` - id of the edge's originating node
-    fn dzdw(
-        &self,
-        iwlayer: usize,
-        ifrom: usize,
-        ito: usize,
-        net: &Network,
-        reference: &Signal
-    ) -> f32 {
-        net.a(iwlayer - 1, ifrom)
-    }
-
-    #[inline]
-    fn dzdb(
-        &self,
-        ilayer: usize,
-        ifrom: usize,
-        ito: usize,
-        net: &Network,
-        reference: &Signal
-    ) -> f32 {
-        1.0
-    }
-
-    /// Calculates partial derivative C by z
-    fn dcdz(
-        &mut self,
-        izlayer: usize,
-        iz: usize,
-        net: &Network,
-        reference: &Signal
-    ) -> f32 {
-        let mut ret = self.net_cache.z(izlayer, iz);
-
-        if ret.is_nan() {
-            let z = net.z(izlayer, iz);
-
-            ret = if izlayer == net.n_layers() - 1 {
-                let ref_z = reference[iz];
-
-                (self.dcdz_output)(ref_z, z)
-            } else {
-                let dcda = self.dcda(izlayer, iz, net, reference);
-                let dadz = (self.dadz)(z);
+    /// Pre: the network must be pre-activated, i.e. the output layer must be
+    /// initialized by forward propagation
+    pub fn accumulate(&mut self, net: &network::Network, reference: &Signal, loss: &dyn Loss) {
+        self.accumulate_with(net, |ilayer| (net.z_vec(ilayer).as_slice(), net.a_vec(ilayer)), reference,
+            loss);
+    }
+
+    /// Same as `accumulate`, but w/ the weighed sums and activations of the
+    /// pass over `input` stored in the workspace rather than in the network
+    fn accumulate_predicted(&mut self, net: &Network, input: &Signal, workspace: &Workspace,
+        reference: &Signal, loss: &dyn Loss)
+    {
+        self.accumulate_with(net,
+            |ilayer| match ilayer {
+                0 => (&[], input),
+                _ => (workspace.z[ilayer - 1].as_slice(), &workspace.a[ilayer - 1]),
+            },
+            reference, loss);
+    }
+
+    /// `layer` yields the weighed sums and activations of a layer
+    fn accumulate_with<'a>(&mut self, net: &Network, layer: impl Fn(usize) -> (&'a [f32], &'a Signal),
+        reference: &Signal, loss: &dyn Loss)
+    {
+        let output = layer(net.n_layers() - 1).1;
+        self.dcda.resize(output.len(), 0.0f32);
+        loss.gradient(reference, output, &mut self.dcda);
+        self.is_output_fused = loss.is_softmax_fused()
+            && net.activation(net.n_layers() - 1) == ActivationFunctionFamily::Softmax;
 
-                dcda * dadz
+        for ilayer in (1..net.n_layers()).rev() {
+            let (z, a) = layer(ilayer);
+            self.layer_delta(net, ilayer, z, a, reference);
+
+            // dC/dw = a_prev * dC/dz, dC/db = dC/dz
+            let delta = &self.deltas[ilayer];
+            kernel::outer_add(layer(ilayer - 1).1, delta, self.gradient.w_matrix_mut(ilayer));
+            kernel::axpy(1.0f32, delta, self.gradient.b_vec_mut(ilayer));
+
+            if ilayer > 1 {
+                self.layer_dcda_prev(net, ilayer);
             }
         }
 
-        self.net_cache.set_z(izlayer, iz, ret);
+        self.n_accumulated += 1;
+    }
 
-        ret
+    /// Sums of dC/dw and dC/db over the samples accumulated since the last
+    /// update, laid out as the network's weights and biases
+    pub fn gradient(&self) -> &Network {
+        &self.gradient
     }
 
-    /// Calculates a partial derivative C by w
-    fn dcdw(
-        &mut self,
-        ilayer: usize,
-        ifrom: usize,
-        ito: usize,
-        net: &network::Network,
-        reference: &Signal
-    ) -> f32 {
-        let mut ret = self.net_cache.w(ilayer, ifrom, ito);
+    /// Adds up the gradients accumulated by `other` for a network of the same
+    /// geometry, and starts its accumulation anew
+    pub fn merge(&mut self, other: &mut BackPropagation) {
+        for ilayer in 1..self.gradient.n_layers() {
+            kernel::axpy(1.0f32, other.gradient.w_matrix(ilayer).as_slice(),
+                self.gradient.w_matrix_mut(ilayer).as_mut_slice());
+            kernel::axpy(1.0f32, other.gradient.b_vec(ilayer), self.gradient.b_vec_mut(ilayer));
+        }
+
+        other.gradient.fill_parameters(0.0f32);
+        self.n_accumulated += other.n_accumulated;
+        other.n_accumulated = 0;
+    }
 
-        // There is nothing in the cache, calculate
-        if ret.is_nan() {
-            // The output layer dc/dz is calculated w/ the use of the user-provided cost function derivative.
-            let dcdz = self.dcdz(ilayer, ito, net, reference);
-            let dzdw = self.dzdw(ilayer, ifrom, ito, net, reference);
-            ret = dcdz * dzdw;
-            self.net_cache.set_w(ilayer, ifrom, ito, ret);
+    /// Same as `accumulate`, but checks that the network is of the geometry
+    /// the back propagation was made for, and that the reference fits it
+    pub fn try_accumulate(&mut self, net: &network::Network, reference: &Signal, loss: &dyn Loss)
+        -> Result<()>
+    {
+        if !net.is_match_geometry(&self.gradient.geometry()) || !net.is_consistent() {
+            return Err(Error::InvalidGeometry(net.geometry()));
         }
 
-        ret
+        Error::check_len(net.try_output_layer()?.len(), reference.len())?;
+        self.accumulate(net, reference, loss);
+
+        Ok(())
     }
 
-    /// Calculates a partial derivative C by b
-    fn dcdb(
-        &mut self,
-        ilayer: usize,
-        ifrom: usize,
-        ito: usize,
-        net: &Network,
-        reference: &Signal
-    ) -> f32 {
-        let mut ret = self.net_cache.b(ilayer, ifrom, ito);
+    /// Averages the gradients over the accumulated samples, and passes them
+    /// to `optimizer` to update weights and biases. Starts accumulation anew.
+    /// Does nothing, if there is nothing accumulated.
+    pub fn apply(&mut self, net: &mut network::Network, optimizer: &mut dyn Optimizer) {
+        if self.n_accumulated == 0 {
+            return;
+        }
+
+        let scale = 1.0f32 / self.n_accumulated as f32;
+        optimizer.begin_step();
 
-        if ret.is_nan() {
-            let dcdz = self.dcdz(ilayer, ito, net, reference);
-            let dzdb = self.dzdb(ilayer, ifrom, ito, net, reference);
-            ret = dcdz * dzdb;
-            self.net_cache.set_b(ilayer, ifrom, ito, ret);
+        for ilayer in 1..net.n_layers() {
+            let dcdw = self.gradient.w_matrix_mut(ilayer).as_mut_slice();
+            kernel::scale(scale, dcdw);
+            optimizer.update(Parameter::Weights(ilayer), net.w_matrix_mut(ilayer).as_mut_slice(), dcdw);
+
+            let dcdb = self.gradient.b_vec_mut(ilayer);
+            kernel::scale(scale, dcdb);
+            optimizer.update(Parameter::Biases(ilayer), net.b_vec_mut(ilayer), dcdb);
         }
 
-        ret
+        self.gradient.fill_parameters(0.0f32);
+        self.n_accumulated = 0;
     }
 
-    /// Train the net using reference output
-    /// `network`: the ANN instance
-    /// `reference` the reference (desired) output
+    /// Train the net using reference output, i.e. performs one optimization
+    /// step on a single sample
     ///
     /// Pre: the network must be pre-activated, i.e. the output layer must be
     /// initialized by forward propagation
-    pub fn run(&mut self, net: &mut network::Network, reference: &Signal) {
-        self.net_cache.reset();
+    pub fn run(&mut self, net: &mut network::Network, reference: &Signal, loss: &dyn Loss,
+            optimizer: &mut dyn Optimizer) {
+        self.accumulate(net, reference, loss);
+        self.apply(net, optimizer);
+    }
 
-        for ilayer in (1..net.n_layers()).rev() {
-            for (ifrom, ito) in net.edge_index_iter(ilayer) {
-                let w = net.w(ilayer, ifrom, ito)
-                    - self.dcdw(ilayer, ifrom, ito, net, reference) * self.epsilon;
-                net.set_w(ilayer, ifrom, ito, w);
-                let b = net.b(ilayer, ifrom, ito)
-                    - self.dcdb(ilayer, ifrom, ito, net, reference) * self.epsilon;
-                net.set_b(ilayer, ifrom, ito, b);
-            }
-        }
+    /// Fallible counterpart of `run`, see `try_accumulate`
+    pub fn try_run(&mut self, net: &mut network::Network, reference: &Signal, loss: &dyn Loss,
+            optimizer: &mut dyn Optimizer) -> Result<()> {
+        self.try_accumulate(net, reference, loss)?;
+        self.apply(net, optimizer);
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test_back_propagation {
-    use super::{BackPropagation, network_init_random, Signal, func, ForwardPropagation};
+    use super::{BackPropagation, network_init_random, Signal, ForwardPropagation,
+        ActivationFunctionFamily, loss};
+    use super::optimizer::Sgd;
     use rand::distributions::{Uniform, Distribution};
     use crate::network::Network;
     use crate::ut;
 
-    fn dcdz_output_stub(reference: f32, actual: f32) -> f32 {
-        f32::NAN
-    }
+    /// Categorical cross-entropy differentiated w/o the softmax shortcut
+    struct UnfusedCrossEntropy;
 
-    fn dadz_stub(dz: f32) -> f32 {
-        f32::NAN
+    impl loss::Loss for UnfusedCrossEntropy {
+        fn value(&self, reference: &Signal, output: &Signal) -> f32 {
+            loss::CategoricalCrossEntropy.value(reference, output)
+        }
+
+        fn gradient(&self, reference: &Signal, output: &Signal, gradient: &mut Signal) {
+            loss::CategoricalCrossEntropy.gradient(reference, output, gradient)
+        }
     }
 
     #[test]
     fn construction() {
         let geometry = vec![128, 16, 32, 4];
         let network = Network::from_geometry(&geometry);
-        let _back_propagation = BackPropagation::from_network(&network,
-            dcdz_output_stub, dadz_stub, 0.01);
+        let _back_propagation = BackPropagation::from_network(&network);
     }
 
     /// Runs full circle, viz. forward and back propagation, to make sure it
@@ -350,59 +605,193 @@ mod test_back_propagation {
     #[test]
     fn run() {
         // Initialize network, its input, and output (reference) signals
+        let mut rng = ut::rng_from_seed(0, ut::RngStream::Initialization);
 
         let geometry = vec![2, 2, 2];
         let mut network = Network::from_geometry(&geometry);
-        network_init_random(&mut network);
+        network_init_random(&mut network, &mut rng);
         let mut signal_input = ut::signal_stub_from_network_input(&network);
         let mut signal_output = ut::signal_stub_from_network_output(&network);
         let epsilon = 0.01f32;
-        ut::vec_init_random(&mut signal_input, 0.0f32, 1.0f32);
-        ut::vec_init_random(&mut signal_output, 0.0f32, 1.0f32);
+        ut::vec_init_random(&mut signal_input, 0.0f32, 1.0f32, &mut rng);
+        ut::vec_init_random(&mut signal_output, 0.0f32, 1.0f32, &mut rng);
 
         // Initialize forward and back propagation algorithms w/ cost and activation functions
-        let mut back_propagation = BackPropagation::from_network(&network,
-            func::cost_mse_d,
-            func::activation_step_d, epsilon);
-        let forward_propagation = ForwardPropagation{activate: func::activation_step};
+        let mut back_propagation = BackPropagation::from_network(&network);
 
         /// Run fwd. and back propagation algorithms
-        forward_propagation.run(&mut network, &signal_input);
-        back_propagation.run(&mut network, &signal_output);
-
-        for ilayer in 1..back_propagation.net_cache.n_layers() {
-            for inode in 0..back_propagation.net_cache.layer_len(ilayer) {
-                assert!(ilayer == back_propagation.net_cache.n_layers() - 1
-                    || !back_propagation.net_cache.a(ilayer, inode).is_nan());  // NAN -> LAST LAYER, or !LAST_LAYER -> !NAN
-                assert!(!back_propagation.net_cache.z(ilayer, inode).is_nan());
-
-                for ifrom in 0..back_propagation.net_cache.layer_len(ilayer - 1) {
-                    assert!(!back_propagation.net_cache.w(ilayer, ifrom, inode).is_nan());
-                    assert!(!back_propagation.net_cache.b(ilayer, ifrom, inode).is_nan());
-                }
+        ForwardPropagation.run(&mut network, &signal_input);
+        back_propagation.run(&mut network, &signal_output, &loss::Sse, &mut Sgd::new(epsilon));
+
+        for ilayer in 1..network.n_layers() {
+            assert_eq!(back_propagation.deltas[ilayer].len(), network.layer_len(ilayer));
+            assert!(back_propagation.deltas[ilayer].iter().all(|delta| delta.is_finite()));
+        }
+    }
+
+    /// NaN is an ordinary value for the backward sweep, not a marker of
+    /// an uncomputed result
+    #[test]
+    fn nan_propagates() {
+        let mut network = Network::from_geometry(&vec![2, 3, 1]);
+        network_init_random(&mut network, &mut ut::rng_from_seed(0, ut::RngStream::Initialization));
+        ForwardPropagation.run(&mut network, &vec![f32::NAN, 0.5]);
+        let mut back_propagation = BackPropagation::from_network(&network);
+        back_propagation.accumulate(&network, &vec![1.0], &loss::Sse);
+
+        assert!(back_propagation.gradient.w(1, 0, 0).is_nan());
+        assert_eq!(back_propagation.n_accumulated, 1);
+    }
+
+    /// A batch of identical samples must produce the same update as the
+    /// sample alone
+    #[test]
+    fn batch_averaging() {
+        let mut rng = ut::rng_from_seed(0, ut::RngStream::Initialization);
+        let geometry = vec![3, 4, 2];
+        let mut network = Network::from_geometry(&geometry);
+        network_init_random(&mut network, &mut rng);
+        let mut network_batch = network.clone();
+        let mut signal_input = ut::signal_stub_from_network_input(&network);
+        let mut signal_output = ut::signal_stub_from_network_output(&network);
+        ut::vec_init_random(&mut signal_input, 0.0f32, 1.0f32, &mut rng);
+        ut::vec_init_random(&mut signal_output, 0.0f32, 1.0f32, &mut rng);
+
+        ForwardPropagation.run(&mut network, &signal_input);
+        BackPropagation::from_network(&network)
+            .run(&mut network, &signal_output, &loss::Sse, &mut Sgd::new(0.1));
+
+        ForwardPropagation.run(&mut network_batch, &signal_input);
+        let mut back_propagation = BackPropagation::from_network(&network_batch);
+        back_propagation.accumulate(&network_batch, &signal_output, &loss::Sse);
+        back_propagation.accumulate(&network_batch, &signal_output, &loss::Sse);
+        back_propagation.apply(&mut network_batch, &mut Sgd::new(0.1));
+
+        for ilayer in 1..network.n_layers() {
+            assert!(ut::vecf32_float_safe_is_eq(network.w_matrix(ilayer).as_slice(),
+                network_batch.w_matrix(ilayer).as_slice()));
+            assert!(ut::vecf32_float_safe_is_eq(network.b_vec(ilayer), network_batch.b_vec(ilayer)));
+        }
+    }
+
+    /// Fused softmax / cross-entropy gradient must agree w/ the one chained
+    /// through the softmax Jacobian
+    #[test]
+    fn softmax_cross_entropy() {
+        let mut rng = ut::rng_from_seed(0, ut::RngStream::Initialization);
+        let geometry = vec![3, 4, 3];
+        let mut network = Network::from_geometry(&geometry);
+        network_init_random(&mut network, &mut rng);
+        network.set_activation(2, ActivationFunctionFamily::Softmax);
+        let mut signal_input = ut::signal_stub_from_network_input(&network);
+        ut::vec_init_random(&mut signal_input, 0.0f32, 1.0f32, &mut rng);
+        let signal_output = vec![0.0f32, 1.0, 0.0];
+        ForwardPropagation.run(&mut network, &signal_input);
+        let output_sum = (0..3).map(|i| network.a(2, i)).sum::<f32>();
+        assert!((output_sum - 1.0).abs() < 1e-5);
+
+        let mut fused = BackPropagation::from_network(&network);
+        let mut chained = BackPropagation::from_network(&network);
+        fused.accumulate(&network, &signal_output, &loss::CategoricalCrossEntropy);
+        chained.accumulate(&network, &signal_output, &UnfusedCrossEntropy);
+
+        for ilayer in 1..network.n_layers() {
+            for (ifrom, ito) in network.edge_index_iter(ilayer) {
+                let (w_fused, w_chained) = (fused.gradient.w(ilayer, ifrom, ito),
+                    chained.gradient.w(ilayer, ifrom, ito));
+                assert!((w_fused - w_chained).abs() < 1e-4, "{} vs {}", w_fused, w_chained);
+            }
+
+            for inode in 0..network.layer_len(ilayer) {
+                assert!((fused.deltas[ilayer][inode] - chained.deltas[ilayer][inode]).abs() < 1e-4);
             }
         }
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum ActivationFunctionFamily {
-    StepFunction = 0,
+    Relu = 0,
+    Sigmoid = 1,
+    Tanh = 2,
+    LeakyRelu = 3,
+    Elu = 4,
+    Gelu = 5,
+    Softplus = 6,
+    Swish = 7,
+    Identity = 8,
+    /// Vector-wise: a_i = e^z_i / sum_j(e^z_j). Has no scalar profile.
+    Softmax = 9,
 }
 
-/// Forward / derivative activation function pairs
-const ACTIVATION_FUNCTION_FAMILY_MAPPING: [(fn(f32) -> f32, fn(f32) -> f32); 1] = [
-    (func::activation_step, func::activation_step_d),
+impl ActivationFunctionFamily {
+    /// All the families, ordered by id
+    pub const ALL: [ActivationFunctionFamily; 10] = [
+        ActivationFunctionFamily::Relu,
+        ActivationFunctionFamily::Sigmoid,
+        ActivationFunctionFamily::Tanh,
+        ActivationFunctionFamily::LeakyRelu,
+        ActivationFunctionFamily::Elu,
+        ActivationFunctionFamily::Gelu,
+        ActivationFunctionFamily::Softplus,
+        ActivationFunctionFamily::Swish,
+        ActivationFunctionFamily::Identity,
+        ActivationFunctionFamily::Softmax,
+    ];
+
+    pub fn from_id(id: usize) -> Option<ActivationFunctionFamily> {
+        ActivationFunctionFamily::ALL.get(id).copied()
+    }
+
+    /// Whether the activation of a node depends on the whole layer, rather
+    /// than on the node's weighted sum only
+    pub fn is_vector_wise(&self) -> bool {
+        *self == ActivationFunctionFamily::Softmax
+    }
+}
+
+/// Families are stored by their numeric ids
+impl Serialize for ActivationFunctionFamily {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+impl<'de> Deserialize<'de> for ActivationFunctionFamily {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<ActivationFunctionFamily, D::Error> {
+        let id = u8::deserialize(deserializer)?;
+
+        ActivationFunctionFamily::from_id(id as usize)
+            .ok_or_else(|| serde::de::Error::custom("unknown activation function family"))
+    }
+}
+
+/// Forward / derivative activation function pairs, indexed by
+/// `ActivationFunctionFamily`
+const ACTIVATION_FUNCTION_FAMILY_MAPPING: [(ActivationFunction, ActivationFunctionDerivative); 9] = [
+    (func::activation_relu, func::activation_relu_d),
+    (func::activation_sigmoid, func::activation_sigmoid_d),
+    (func::activation_tanh, func::activation_tanh_d),
+    (func::activation_leaky_relu, func::activation_leaky_relu_d),
+    (func::activation_elu, func::activation_elu_d),
+    (func::activation_gelu, func::activation_gelu_d),
+    (func::activation_softplus, func::activation_softplus_d),
+    (func::activation_swish, func::activation_swish_d),
+    (func::activation_identity, func::activation_identity_d),
 ];
 
-/// Encapsulates training / recognition profile
-struct ActivationProfile {
-    activation_function: fn(f32) -> f32,
-    activation_function_derivative: fn(f32) -> f32,
+/// Encapsulates training / recognition profile of a scalar activation function
+#[derive(Clone, Copy)]
+pub struct ActivationProfile {
+    pub activation_function: ActivationFunction,
+    pub activation_function_derivative: ActivationFunctionDerivative,
 }
 
 impl ActivationProfile {
-    fn new(activation_function_family: ActivationFunctionFamily) -> ActivationProfile {
+    /// Pre: `activation_function_family` is not vector-wise
+    pub const fn new(activation_function_family: ActivationFunctionFamily) -> ActivationProfile {
         let id = activation_function_family as usize;
+        assert!(id < ACTIVATION_FUNCTION_FAMILY_MAPPING.len(), "Not a scalar activation function");
         ActivationProfile {
             activation_function: ACTIVATION_FUNCTION_FAMILY_MAPPING[id].0,
             activation_function_derivative:
@@ -411,54 +800,801 @@ impl ActivationProfile {
     }
 }
 
-pub fn train_network_back_propagation<F>(net: &mut Network,
-    activation_function: ActivationFunction,
-    activation_function_derivative: ActivationFunctionDerivative,
-    cost_function_derivative: CostFunctionDerivative,
-    training_rate: f32,
+#[cfg(test)]
+mod test_activation_profile {
+    use super::{ActivationProfile, ActivationFunctionFamily};
+
+    /// Compares derivatives against central finite differences
+    #[test]
+    fn derivatives() {
+        const H: f32 = 1e-3;
+
+        for family in ActivationFunctionFamily::ALL.into_iter().filter(|f| !f.is_vector_wise()) {
+            let profile = ActivationProfile::new(family);
+
+            // Kinks of piecewise functions at 0 are avoided
+            for z in [-3.0f32, -1.1, -0.3, 0.2, 0.9, 2.7] {
+                let numeric = ((profile.activation_function)(z + H)
+                    - (profile.activation_function)(z - H)) / (2.0 * H);
+                let analytic = (profile.activation_function_derivative)(z);
+                assert!((numeric - analytic).abs() < 1e-2, "{:?} at {}: {} vs {}", family, z,
+                    numeric, analytic);
+            }
+        }
+    }
+}
+
+/// Worker of data-parallel training w/ its own activations and gradient
+/// buffers. Propagates its share of every batch w/ the parameters of the
+/// trained network.
+struct Worker {
+    workspace: Workspace,
+    back_propagation: BackPropagation,
+    input_signal: Signal,
+    /// Indices of the samples of the ongoing batch's share
+    samples: Vec<usize>,
+    /// Index, reference, and output of each sample propagated during the
+    /// ongoing batch
+    propagated: Vec<(usize, Signal, Signal)>,
+}
+
+impl Worker {
+    fn from_network(net: &Network) -> Worker {
+        Worker{
+            workspace: Workspace::from_network(net),
+            back_propagation: BackPropagation::from_network(net),
+            input_signal: ut::signal_stub_from_network_input(net),
+            samples: Vec::new(),
+            propagated: Vec::new(),
+        }
+    }
+
+    /// Accumulates gradients over the share's samples w/ the parameters of
+    /// `net`
+    fn propagate(&mut self, net: &Network, loss: &dyn Loss, dataset: &impl ut::data::Dataset) {
+        self.propagated.resize(self.samples.len(), (0, Signal::new(), Signal::new()));
+
+        for (isample, (i, reference, output)) in self.samples.iter().zip(self.propagated.iter_mut()) {
+            dataset.copy_training_input_signal(*isample, &mut self.input_signal);
+            ForwardPropagation.predict(net, &self.input_signal, &mut self.workspace);
+            dataset.copy_training_output_signal(*isample, reference);
+            self.back_propagation.accumulate_predicted(net, &self.input_signal, &self.workspace, reference,
+                loss);
+            *i = *isample;
+            output.clone_from(self.workspace.output());
+        }
+    }
+}
+
+/// Workers of a training session, each running on a thread of its own until
+/// the pool is dropped. A worker is handed its share through its mutex, and
+/// the start and the end of its propagation are signaled through channels.
+struct WorkerPool<'a> {
+    workers: &'a [Mutex<Worker>],
+    /// Senders of the start signal and receivers of the end one, by worker
+    channels: Vec<(mpsc::Sender<()>, mpsc::Receiver<()>)>,
+}
+
+impl WorkerPool<'_> {
+    fn is_empty(&self) -> bool {
+        self.workers.is_empty()
+    }
+
+    /// Splits the batch into contiguous shares, one per worker, and has them
+    /// propagated in parallel. `on_propagated` receives each worker that got
+    /// a share, in the order of the workers.
+    fn propagate(&self, batch: &[usize], mut on_propagated: impl FnMut(&mut Worker)) {
+        let shares = batch.chunks(batch.len().div_ceil(self.workers.len()));
+        let n_shares = shares.len();
+
+        for ((worker, (start, _)), samples) in self.workers.iter().zip(&self.channels).zip(shares) {
+            let mut worker = worker.lock().unwrap();
+            worker.samples.clear();
+            worker.samples.extend_from_slice(samples);
+            start.send(()).expect("Worker thread has stopped");
+        }
+
+        for (worker, (_, end)) in self.workers.iter().zip(&self.channels).take(n_shares) {
+            end.recv().expect("Worker thread has stopped");
+            on_propagated(&mut worker.lock().unwrap());
+        }
+    }
+}
+
+/// Runs a training session of `net` w/ workers for data-parallel training on
+/// `n_threads` threads. The threads are started once, and read the network
+/// while the session is not holding it for writing. A single thread needs no
+/// workers, the network is trained in place.
+fn with_workers<R>(net: &mut Network,
+    n_threads: usize,
+    loss: &dyn Loss,
+    dataset: &(impl ut::data::Dataset + Sync),
+    session: impl FnOnce(&RwLock<&mut Network>, &WorkerPool) -> R) -> R
+{
+    let n_workers = if n_threads > 1 { n_threads } else { 0 };
+    let workers: Vec<Mutex<Worker>> = (0..n_workers).map(|_| Mutex::new(Worker::from_network(net))).collect();
+    let net = RwLock::new(net);
+
+    std::thread::scope(|scope| {
+        let channels = workers.iter()
+            .map(|worker| {
+                let (start_sender, start) = mpsc::channel();
+                let (end, end_receiver) = mpsc::channel();
+                let net = &net;
+
+                // Stops once the pool, and so the start sender, is dropped
+                scope.spawn(move || {
+                    while start.recv().is_ok() {
+                        worker.lock().unwrap().propagate(&net.read().unwrap(), loss, dataset);
+
+                        if end.send(()).is_err() {
+                            break;
+                        }
+                    }
+                });
+
+                (start_sender, end_receiver)
+            })
+            .collect();
+
+        session(&net, &WorkerPool{workers: &workers, channels})
+    })
+}
+
+/// Runs forward and back propagation over the samples of `dataset` in the
+/// order provided by `order`, applying the accumulated gradients once per
+/// `batch_size` samples, and once more after the last sample.
+/// `on_sample_propagated` receives the sample index, the reference, and the
+/// network's output before the update. `on_batch_applied` receives the
+/// updated network, and the number of samples taken from `order` so far.
+///
+/// W/ workers, each batch is split into contiguous shares, one per worker,
+/// which are propagated in parallel. Gradients are then summed up in the
+/// order of the workers, so the outcome only depends on the number of them.
+#[allow(clippy::too_many_arguments)]
+fn train_network_samples(net: &RwLock<&mut Network>,
+    back_propagation: &mut BackPropagation,
+    workers: &WorkerPool,
+    loss: &dyn Loss,
+    optimizer: &mut dyn Optimizer,
+    batch_size: usize,
     dataset: &impl ut::data::Dataset,
+    order: &[usize],
+    on_sample_propagated: &mut dyn FnMut(usize, &Signal, &Signal),
+    on_batch_applied: &mut dyn FnMut(&Network, &dyn Optimizer, usize))
+{
+    assert!(batch_size > 0);
+    let mut input_signal = ut::signal_stub_from_network_input(&net.read().unwrap());
+    let mut output_signal_reference = ut::signal_stub_from_network_output(&net.read().unwrap());
+    let mut n_samples = 0;
+
+    for batch in order.chunks(batch_size) {
+        if workers.is_empty() {
+            let net = &mut **net.write().unwrap();
+
+            for isample in batch {
+                dataset.copy_training_input_signal(*isample, &mut input_signal);
+                ForwardPropagation.run(net, &input_signal);
+                dataset.copy_training_output_signal(*isample, &mut output_signal_reference);
+                back_propagation.accumulate(net, &output_signal_reference, loss);
+                on_sample_propagated(*isample, &output_signal_reference, net.output_layer());
+            }
+        } else {
+            workers.propagate(batch, |worker| {
+                back_propagation.merge(&mut worker.back_propagation);
+
+                for (isample, reference, output) in &worker.propagated {
+                    on_sample_propagated(*isample, reference, output);
+                }
+            });
+        }
+
+        let net = &mut **net.write().unwrap();
+        n_samples += batch.len();
+        back_propagation.apply(net, optimizer);
+        on_batch_applied(net, optimizer, n_samples);
+    }
+}
+
+/// Trains the network using mini-batch gradient descent. Weights are updated
+/// by `optimizer` once per `batch_size` samples w/ gradients averaged over the
+/// batch. The last batch may be incomplete. Batches are split across
+/// `n_threads` threads.
+pub fn train_network_back_propagation<F>(net: &mut Network,
+    loss: &dyn Loss,
+    optimizer: &mut dyn Optimizer,
+    batch_size: usize,
+    n_threads: usize,
+    dataset: &(impl ut::data::Dataset + Sync),
     on_iteration_ended_hook: F)
 where
     F: Fn(usize)
 {
-    let mut forward_propagation = ForwardPropagation{activate: activation_function};
-    let mut back_propagation = BackPropagation::from_network(net,
-        cost_function_derivative, activation_function_derivative, training_rate);
-    let mut input_signal = ut::signal_stub_from_network_input(net);
-    let mut output_signal_reference = ut::signal_stub_from_network_output(net);
+    let mut back_propagation = BackPropagation::from_network(net);
+    let order = (0..dataset.length()).collect::<Vec<usize>>();
+
+    with_workers(net, n_threads, loss, dataset, |net, workers| {
+        train_network_samples(net, &mut back_propagation, workers, loss, optimizer,
+            batch_size, dataset, &order,
+            &mut |isample, _, _| on_iteration_ended_hook(isample), &mut |_, _, _| {});
+    });
+}
+
+/// Hyperparameters of an epoch-based training session
+#[derive(Clone, Debug)]
+pub struct TrainingParameters {
+    pub n_epochs: usize,
+    pub batch_size: usize,
+    /// Seed of the session. Samples are shuffled before each epoch w/ the
+    /// `ut::RngStream::Shuffling` stream of it.
+    pub seed: u64,
+    /// Number of threads each batch is split across. Sessions w/ the same
+    /// seed and number of threads yield identical networks.
+    pub n_threads: usize,
+}
+
+/// Summary of a finished training epoch
+#[derive(Clone, Debug)]
+pub struct EpochReport {
+    /// Index of the epoch, starting from 0
+    pub epoch: usize,
+    /// Cost averaged over the samples of the epoch. Each sample's cost is
+    /// measured before the update the sample contributes to. The optimizer's
+    /// regularization term, if any, is measured after the update.
+    pub mean_loss: f32,
+    /// Cost averaged over the validation samples after the epoch, if the
+    /// session is validated
+    pub validation_loss: Option<f32>,
+}
+
+/// Position within an epoch-based training session. Along w/ the network,
+/// the optimizer state, and the parameters, it is sufficient to resume the
+/// session.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct TrainingProgress {
+    /// Index of the ongoing epoch
+    pub epoch: usize,
+    /// Number of the epoch's samples already trained on
+    pub cursor: usize,
+    /// Sum of the cost over those samples
+    pub loss_sum: f32,
+}
+
+impl TrainingProgress {
+    /// Checks that a session w/ the parameters over a dataset of
+    /// `dataset_length` samples can be resumed from the progress, i.e. that
+    /// the cursor is at a batch boundary within the dataset
+    pub fn check(&self, parameters: &TrainingParameters, dataset_length: usize) -> Result<()> {
+        if parameters.batch_size == 0 {
+            return Err(Error::InconsistentData("batch size of 0".into()));
+        }
+
+        if self.cursor > dataset_length {
+            return Err(Error::InconsistentData(format!("cursor {} is past the dataset of {} samples",
+                self.cursor, dataset_length)));
+        }
+
+        if !self.cursor.is_multiple_of(parameters.batch_size) && self.cursor != dataset_length {
+            return Err(Error::InconsistentData(format!("cursor {} is not at a boundary of {} sample batches",
+                self.cursor, parameters.batch_size)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Trains the network for `parameters.n_epochs` epochs, each being a pass
+/// over the whole dataset in an order reshuffled before the epoch. Same seed
+/// yields same sample orders.
+pub fn train_network_epochs<F>(net: &mut Network,
+    loss: &dyn Loss,
+    optimizer: &mut dyn Optimizer,
+    parameters: &TrainingParameters,
+    dataset: &(impl ut::data::Dataset + Sync),
+    mut on_epoch_ended_hook: F) -> Vec<EpochReport>
+where
+    F: FnMut(&EpochReport)
+{
+    train_network_epochs_until(net, loss, optimizer, parameters, TrainingProgress::default(), dataset,
+        &mut |report, _| {
+            on_epoch_ended_hook(report);
+            ControlFlow::Continue(())
+        },
+        &mut |_, _, _| {})
+}
+
+/// Continues a session of `train_network_epochs` from `progress`, which must
+/// have been reported by `on_batch_applied_hook` of an earlier run w/ the
+/// same parameters and dataset. The hook receives the progress after each
+/// update of the network.
+///
+/// The shuffling generator's state is restored by replaying the shuffles of
+/// the finished epochs, so resumed and uninterrupted sessions are identical.
+/// Progress which does not fit the parameters and the dataset is reported,
+/// see `TrainingProgress::check`.
+#[allow(clippy::too_many_arguments)]
+pub fn train_network_epochs_resumed<F, C>(net: &mut Network,
+    loss: &dyn Loss,
+    optimizer: &mut dyn Optimizer,
+    parameters: &TrainingParameters,
+    progress: TrainingProgress,
+    dataset: &(impl ut::data::Dataset + Sync),
+    mut on_epoch_ended_hook: F,
+    mut on_batch_applied_hook: C) -> Result<Vec<EpochReport>>
+where
+    F: FnMut(&EpochReport),
+    C: FnMut(&TrainingProgress, &Network, &dyn Optimizer),
+{
+    progress.check(parameters, dataset.length())?;
+
+    Ok(train_network_epochs_until(net, loss, optimizer, parameters, progress, dataset,
+        &mut |report, _| {
+            on_epoch_ended_hook(report);
+            ControlFlow::Continue(())
+        },
+        &mut on_batch_applied_hook))
+}
+
+/// `train_network_epochs_resumed`, which stops once `on_epoch_ended` breaks.
+/// The hook may amend the report before it is stored.
+#[allow(clippy::too_many_arguments)]
+fn train_network_epochs_until(net: &mut Network,
+    loss: &dyn Loss,
+    optimizer: &mut dyn Optimizer,
+    parameters: &TrainingParameters,
+    progress: TrainingProgress,
+    dataset: &(impl ut::data::Dataset + Sync),
+    on_epoch_ended: &mut dyn FnMut(&mut EpochReport, &Network) -> ControlFlow<()>,
+    on_batch_applied: &mut dyn FnMut(&TrainingProgress, &Network, &dyn Optimizer)) -> Vec<EpochReport>
+{
+    assert!(progress.cursor.is_multiple_of(parameters.batch_size) || progress.cursor == dataset.length());
+    let mut back_propagation = BackPropagation::from_network(net);
+    let mut rng = ut::rng_from_seed(parameters.seed, ut::RngStream::Shuffling);
+    let mut order = (0..dataset.length()).collect::<Vec<usize>>();
+    let mut reports = Vec::new();
+    reports.reserve_exact(parameters.n_epochs.saturating_sub(progress.epoch));
+
+    for _ in 0..progress.epoch {
+        order.shuffle(&mut rng);
+    }
+
+    with_workers(net, parameters.n_threads, loss, dataset, |net, workers| {
+        for epoch in progress.epoch..parameters.n_epochs {
+            let (cursor, loss_sum) = if epoch == progress.epoch {
+                (progress.cursor, progress.loss_sum)
+            } else {
+                (0, 0.0f32)
+            };
+            // Shared by the hooks
+            let loss_sum = std::cell::Cell::new(loss_sum);
+            let n_samples_applied = std::cell::Cell::new(0usize);
+            order.shuffle(&mut rng);
+            train_network_samples(net, &mut back_propagation, workers, loss, optimizer,
+                parameters.batch_size, dataset, &order[cursor..],
+                &mut |_, reference, output| loss_sum.set(loss_sum.get() + loss.value(reference, output)),
+                &mut |net, optimizer, n_samples| {
+                    // The regularization term is measured once per batch, and counts for each sample of it
+                    let n_batch_samples = n_samples - n_samples_applied.replace(n_samples);
+                    loss_sum.set(loss_sum.get() + optimizer.penalty(net) * n_batch_samples as f32);
+                    let cursor = cursor + n_samples;
+                    let progress = if cursor == order.len() {
+                        TrainingProgress{epoch: epoch + 1, cursor: 0, loss_sum: 0.0f32}
+                    } else {
+                        TrainingProgress{epoch, cursor, loss_sum: loss_sum.get()}
+                    };
+                    on_batch_applied(&progress, net, optimizer);
+                });
+            let mut report = EpochReport{
+                epoch,
+                mean_loss: loss_sum.get() / order.len().max(1) as f32,
+                validation_loss: None,
+            };
+            let flow = on_epoch_ended(&mut report, &net.read().unwrap());
+            optimizer.end_epoch(report.validation_loss);
+            reports.push(report);
+
+            if flow.is_break() {
+                break;
+            }
+        }
+    });
+
+    reports
+}
+
+/// Early stopping criterion of a validated training session
+#[derive(Clone, Debug)]
+pub struct EarlyStopping {
+    /// Number of epochs in a row w/o improvement of the validation loss
+    /// after which the session stops, i.e. it stops once the count reaches
+    /// the patience. `schedule::ReduceOnPlateau` counts it the same way.
+    pub patience: usize,
+    /// The least decrease of the validation loss counted as improvement
+    pub min_delta: f32,
+}
+
+/// Outcome of a validated training session
+#[derive(Clone, Debug)]
+pub struct ValidatedTrainingReport {
+    pub epochs: Vec<EpochReport>,
+    /// Epoch whose network has the lowest finite validation loss. The
+    /// trained network is restored to it. If there is no such epoch, the
+    /// network is restored to its state before the session, and the loss is
+    /// NaN.
+    pub best_epoch: usize,
+    pub best_validation_loss: f32,
+    /// Whether the session was stopped before `n_epochs`
+    pub is_stopped_early: bool,
+}
+
+/// Trains the network as `train_network_epochs` does, measuring the loss on
+/// `validation` after each epoch. Stops once the validation loss has not
+/// improved for `early_stopping.patience` epochs. At the end, the network is
+/// restored to the parameters it had after the best epoch.
+#[allow(clippy::too_many_arguments)]
+pub fn train_network_validated<F>(net: &mut Network,
+    loss: &dyn Loss,
+    optimizer: &mut dyn Optimizer,
+    parameters: &TrainingParameters,
+    early_stopping: &EarlyStopping,
+    training: &(impl ut::data::Dataset + Sync),
+    validation: &impl ut::data::Dataset,
+    mut on_epoch_ended_hook: F) -> ValidatedTrainingReport
+where
+    F: FnMut(&EpochReport)
+{
+    let mut best_network = net.clone();
+    let mut best: Option<(usize, f32)> = None;
+    let mut n_epochs_stale = 0usize;
+    let epochs = train_network_epochs_until(net, loss, optimizer, parameters, TrainingProgress::default(),
+        training,
+        &mut |report, net| {
+            let validation_loss = test_network_forward_propagation(net, loss, validation, |_, _| {});
+            report.validation_loss = Some(validation_loss);
+            on_epoch_ended_hook(report);
+
+            // A non-finite loss, e.g. of a diverged network, is never an improvement
+            let is_improved = validation_loss.is_finite()
+                && best.is_none_or(|(_, best_loss)| validation_loss < best_loss - early_stopping.min_delta);
+
+            if is_improved {
+                best = Some((report.epoch, validation_loss));
+                best_network.copy_parameters_from(net);
+                n_epochs_stale = 0;
+
+                return ControlFlow::Continue(());
+            }
+
+            n_epochs_stale += 1;
+
+            if n_epochs_stale >= early_stopping.patience {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        },
+        &mut |_, _, _| {});
 
-    for i in 0..dataset.length() {
-        dataset.copy_training_input_signal(i, &mut input_signal);
-        forward_propagation.run(net, &input_signal);
-        dataset.copy_training_output_signal(i, &mut output_signal_reference);
-        back_propagation.run(net, &output_signal_reference);
-        on_iteration_ended_hook(i);
+    let (best_epoch, best_validation_loss) = best.unwrap_or((0, f32::NAN));
+    net.copy_parameters_from(&best_network);
+
+    ValidatedTrainingReport{
+        is_stopped_early: epochs.len() < parameters.n_epochs,
+        epochs,
+        best_epoch,
+        best_validation_loss,
+    }
+}
+
+/// `train_network_validated`, w/ `n_validation` samples of `dataset` drawn
+/// for validation by the `ut::RngStream::Holdout` stream of the session's
+/// seed, and the rest used for training
+#[allow(clippy::too_many_arguments)]
+pub fn train_network_early_stopping<F>(net: &mut Network,
+    loss: &dyn Loss,
+    optimizer: &mut dyn Optimizer,
+    parameters: &TrainingParameters,
+    early_stopping: &EarlyStopping,
+    n_validation: usize,
+    dataset: &(impl ut::data::Dataset + Sync),
+    on_epoch_ended_hook: F) -> ValidatedTrainingReport
+where
+    F: FnMut(&EpochReport)
+{
+    let (training, validation) = ut::data::Subset::split_holdout(dataset, n_validation,
+        &mut ut::rng_from_seed(parameters.seed, ut::RngStream::Holdout));
+
+    train_network_validated(net, loss, optimizer, parameters, early_stopping, &training, &validation,
+        on_epoch_ended_hook)
+}
+
+#[cfg(test)]
+mod test_training {
+    use super::*;
+    use super::optimizer::Sgd;
+
+    /// Learn to sum two numbers
+    struct SumDataset {
+        samples: Vec<(Signal, Signal)>,
+    }
+
+    impl SumDataset {
+        fn new(len: usize) -> SumDataset {
+            let samples = (0..len)
+                .map(|i| {
+                    let x = (i % 7) as f32 / 7.0;
+                    let y = (i % 5) as f32 / 5.0;
+                    (vec![x, y], vec![x + y])
+                })
+                .collect();
+
+            SumDataset{samples}
+        }
+    }
+
+    impl ut::data::Dataset for SumDataset {
+        fn copy_training_input_signal(&self, image_index: usize, signal: &mut Signal) {
+            signal.clone_from(&self.samples[image_index].0);
+        }
+
+        fn copy_training_output_signal(&self, image_index: usize, signal: &mut Signal) {
+            signal.clone_from(&self.samples[image_index].1);
+        }
+
+        fn length(&self) -> usize {
+            self.samples.len()
+        }
+    }
+
+    fn train(seed: u64, n_threads: usize) -> (Network, Vec<EpochReport>) {
+        let mut network = Network::from_geometry(&vec![2, 3, 1]);
+        init::network_init(&mut network, init::WeightInit::XavierUniform, init::BiasInit::Zeros,
+            &mut ut::rng_from_seed(seed, ut::RngStream::Initialization));
+        let parameters = TrainingParameters{n_epochs: 20, batch_size: 4, seed, n_threads};
+        let reports = train_network_epochs(&mut network, &loss::Sse, &mut Sgd::new(0.05),
+            &parameters, &SumDataset::new(35), |_| {});
+
+        (network, reports)
+    }
+
+    #[test]
+    fn epochs() {
+        let (network, reports) = train(1, 1);
+        assert_eq!(reports.len(), 20);
+        assert!(reports.last().unwrap().mean_loss < reports[0].mean_loss);
+        assert!(train(1, 1).0 == network);
+    }
+
+    /// Splitting batches across threads changes the summation order only
+    #[test]
+    fn parallel() {
+        let (network, reports) = train(1, 3);
+        let (network_sequential, reports_sequential) = train(1, 1);
+        assert!(train(1, 3).0 == network);
+
+        for (report, report_sequential) in reports.iter().zip(&reports_sequential) {
+            assert!((report.mean_loss - report_sequential.mean_loss).abs() < 1e-4);
+        }
+
+        for ilayer in 1..network.n_layers() {
+            for (w, w_sequential) in network.w_matrix(ilayer).as_slice().iter()
+                .zip(network_sequential.w_matrix(ilayer).as_slice())
+            {
+                assert!((w - w_sequential).abs() < 1e-4);
+            }
+        }
+    }
+
+    /// A session interrupted after a batch and resumed w/ the captured
+    /// state must end up where an uninterrupted one does
+    #[test]
+    fn resume() {
+        let dataset = SumDataset::new(35);
+        let parameters = TrainingParameters{n_epochs: 5, batch_size: 4, seed: 3, n_threads: 1};
+        let mut network = Network::from_geometry(&vec![2, 3, 1]);
+        init::network_init(&mut network, init::WeightInit::XavierUniform, init::BiasInit::Zeros,
+            &mut ut::rng_from_seed(3, ut::RngStream::Initialization));
+        let mut checkpoint = None;
+        let reports = train_network_epochs_resumed(&mut network, &loss::Sse,
+            &mut optimizer::Momentum::new(0.01, 0.9), &parameters, TrainingProgress::default(),
+            &dataset, |_| {},
+            |progress, net, optimizer| {
+                if progress.epoch == 2 && progress.cursor == 12 {
+                    checkpoint = Some((*progress, net.clone(), optimizer.export_state()));
+                }
+            }).unwrap();
+        let (progress, mut network_resumed, optimizer_state) = checkpoint.unwrap();
+        let mut optimizer = optimizer::Momentum::new(0.0, 0.0);
+        optimizer.import_state(&optimizer_state, &network_resumed).unwrap();
+        let reports_resumed = train_network_epochs_resumed(&mut network_resumed, &loss::Sse,
+            &mut optimizer, &parameters, progress, &dataset, |_| {}, |_, _, _| {}).unwrap();
+
+        assert!(network_resumed == network);
+        assert_eq!(reports_resumed.len(), 3);
+        assert_eq!(reports_resumed[0].mean_loss, reports[2].mean_loss);
+
+        for (cursor, batch_size) in [(36, 4), (10, 4), (0, 0)] {
+            let progress = TrainingProgress{epoch: 2, cursor, loss_sum: 0.0};
+            let parameters = TrainingParameters{batch_size, ..parameters.clone()};
+            assert!(matches!(train_network_epochs_resumed(&mut network_resumed, &loss::Sse, &mut optimizer,
+                &parameters, progress, &dataset, |_| {}, |_, _, _| {}), Err(Error::InconsistentData(_))),
+                "cursor {}, batch size {}", cursor, batch_size);
+        }
+    }
+
+    /// Validation targets oppose the training ones, so the validation loss
+    /// soon stops improving
+    #[test]
+    fn early_stopping() {
+        let dataset = SumDataset::new(35);
+        let mut validation = SumDataset::new(10);
+        validation.samples.iter_mut().for_each(|(_, reference)| reference[0] = -reference[0]);
+        let parameters = TrainingParameters{n_epochs: 50, batch_size: 4, seed: 1, n_threads: 1};
+        let mut network = Network::from_geometry(&vec![2, 3, 1]);
+        init::network_init(&mut network, init::WeightInit::XavierUniform, init::BiasInit::Zeros,
+            &mut ut::rng_from_seed(1, ut::RngStream::Initialization));
+        let report = train_network_validated(&mut network, &loss::Sse, &mut Sgd::new(0.05), &parameters,
+            &EarlyStopping{patience: 3, min_delta: 0.0}, &dataset, &validation, |_| {});
+
+        assert!(report.is_stopped_early);
+        assert_eq!(report.epochs.len(), report.best_epoch + 4);
+        assert_eq!(report.epochs[report.best_epoch].validation_loss, Some(report.best_validation_loss));
+        assert_eq!(test_network_forward_propagation(&network, &loss::Sse, &validation, |_, _| {}),
+            report.best_validation_loss);
+    }
+
+    /// Samples which do not fit the network must be reported
+    #[test]
+    fn test_shape_mismatch() {
+        let dataset = SumDataset::new(5);
+        let result = try_test_network_forward_propagation(&Network::from_geometry(&vec![3, 1]), &loss::Sse,
+            &dataset, |_, _| {});
+        assert!(matches!(result, Err(Error::ShapeMismatch{expected: 3, actual: 2})));
+        let result = try_test_network_forward_propagation(&Network::from_geometry(&vec![2, 2]), &loss::Sse,
+            &dataset, |_, _| {});
+        assert!(matches!(result, Err(Error::ShapeMismatch{expected: 2, actual: 1})));
+    }
+
+    /// Validation set whose references are NaN during the first epoch
+    struct NanFirstEpoch {
+        dataset: SumDataset,
+        n_references: std::sync::atomic::AtomicUsize,
+    }
+
+    impl ut::data::Dataset for NanFirstEpoch {
+        fn copy_training_input_signal(&self, image_index: usize, signal: &mut Signal) {
+            self.dataset.copy_training_input_signal(image_index, signal);
+        }
+
+        fn copy_training_output_signal(&self, image_index: usize, signal: &mut Signal) {
+            self.dataset.copy_training_output_signal(image_index, signal);
+
+            if self.n_references.fetch_add(1, std::sync::atomic::Ordering::Relaxed) < self.length() {
+                signal.fill(f32::NAN);
+            }
+        }
+
+        fn length(&self) -> usize {
+            self.dataset.length()
+        }
+    }
+
+    /// A NaN validation loss must neither become the best one, nor reset
+    /// the patience
+    #[test]
+    fn early_stopping_nan() {
+        let validation = NanFirstEpoch{dataset: SumDataset::new(10), n_references: 0.into()};
+        let parameters = TrainingParameters{n_epochs: 10, batch_size: 4, seed: 1, n_threads: 1};
+        let mut network = Network::from_geometry(&vec![2, 3, 1]);
+        init::network_init(&mut network, init::WeightInit::XavierUniform, init::BiasInit::Zeros,
+            &mut ut::rng_from_seed(1, ut::RngStream::Initialization));
+        let report = train_network_validated(&mut network, &loss::Sse, &mut Sgd::new(0.05), &parameters,
+            &EarlyStopping{patience: 2, min_delta: 0.0}, &SumDataset::new(35), &validation, |_| {});
+
+        assert!(report.epochs[0].validation_loss.unwrap().is_nan());
+        assert!(report.best_epoch > 0 && report.best_validation_loss.is_finite());
+        assert_eq!(report.epochs[report.best_epoch].validation_loss, Some(report.best_validation_loss));
+    }
+
+    /// W/ a zero rate, the network stays intact, and the reported loss is
+    /// its cost plus the penalty
+    #[test]
+    fn regularized_loss() {
+        let dataset = SumDataset::new(10);
+        let mut network = Network::from_geometry(&vec![2, 3, 1]);
+        init::network_init(&mut network, init::WeightInit::XavierUniform, init::BiasInit::Zeros,
+            &mut ut::rng_from_seed(1, ut::RngStream::Initialization));
+        let mut optimizer = regularization::Regularized::new(Sgd::new(0.0),
+            regularization::Regularization{l2: 1.0, ..Default::default()});
+        let cost = test_network_forward_propagation(&network, &loss::Sse, &dataset, |_, _| {});
+        let penalty = optimizer.penalty(&network);
+        let parameters = TrainingParameters{n_epochs: 1, batch_size: 3, seed: 1, n_threads: 1};
+        let reports = train_network_epochs(&mut network, &loss::Sse, &mut optimizer, &parameters, &dataset,
+            |_| {});
+
+        assert!(penalty > 0.0);
+        assert!((reports[0].mean_loss - (cost + penalty)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn holdout() {
+        let dataset = SumDataset::new(35);
+        let (training, validation) = ut::data::Subset::split_holdout(&dataset, 10,
+            &mut ut::rng_from_seed(0, ut::RngStream::Holdout));
+        let mut indices = [training.indices(), validation.indices()].concat();
+        indices.sort();
+
+        assert_eq!(validation.indices().len(), 10);
+        assert_eq!(indices, (0..35).collect::<Vec<usize>>());
     }
 }
 
 pub fn run_network_forward_propagation<'a>(net: &'a mut Network,
-        activation_function: ActivationFunction,
         input_signal: &Signal) -> &'a Signal {
-    let forward_propagation = ForwardPropagation{activate: activation_function};
-    forward_propagation.run(net, &input_signal);
+    ForwardPropagation.run(net, input_signal);
 
     net.output_layer()
 }
 
-pub fn test_network_forward_propagation<F>(net: &mut Network,
-    activation_function: ActivationFunction,
+/// Number of samples evaluated at once by `test_network_forward_propagation`
+const TEST_BATCH_SIZE: usize = 64;
+
+/// Runs forward propagation over the dataset in batches. Returns the value of
+/// `loss` averaged over the samples. The network is left intact.
+pub fn test_network_forward_propagation<F>(net: &Network,
+    loss: &dyn Loss,
     dataset: &impl ut::data::Dataset,
-    on_iteration_ended_hook: F,)
+    on_iteration_ended_hook: F,) -> f32
 where
-    for <'a> F: Fn( &'a Signal, /* Expected */ &'a Signal /* Network output */)
+    for <'a> F: FnMut( &'a Signal, /* Expected */ &'a Signal /* Network output */)
 {
+    match try_test_network_forward_propagation(net, loss, dataset, on_iteration_ended_hook) {
+        Ok(mean_loss) => mean_loss,
+        Err(e) => panic!("Failed to evaluate the network: {}", e),
+    }
+}
+
+/// Same as `test_network_forward_propagation`, but reports an inconsistent
+/// network, or samples which do not fit it, instead of panicking
+pub fn try_test_network_forward_propagation<F>(net: &Network,
+    loss: &dyn Loss,
+    dataset: &impl ut::data::Dataset,
+    mut on_iteration_ended_hook: F,) -> Result<f32>
+where
+    for <'a> F: FnMut( &'a Signal, /* Expected */ &'a Signal /* Network output */)
+{
+    if net.n_layers() < 2 || !net.is_consistent() {
+        return Err(Error::InvalidGeometry(net.geometry()));
+    }
+
     let mut input_signal = ut::signal_stub_from_network_input(net);
+    let mut output_signal = ut::signal_stub_from_network_output(net);
     let mut output_signal_reference = ut::signal_stub_from_network_output(net);
+    let (input_len, output_len) = (input_signal.len(), output_signal.len());
+    let mut inputs = network::Matrix::new(0, 0, 0.0f32);
+    let mut workspace = BatchWorkspace::default();
+    let mut loss_sum = 0.0f32;
+
+    for ibegin in (0..dataset.length()).step_by(TEST_BATCH_SIZE) {
+        let iend = dataset.length().min(ibegin + TEST_BATCH_SIZE);
+        inputs.reshape(iend - ibegin, input_len);
+
+        for isample in ibegin..iend {
+            dataset.copy_training_input_signal(isample, &mut input_signal);
+            Error::check_len(input_len, input_signal.len())?;
+            inputs.row_mut(isample - ibegin).copy_from_slice(&input_signal);
+        }
+
+        let outputs = ForwardPropagation.try_predict_batch(net, &inputs, &mut workspace)?;
 
-    for i in 0..dataset.length() {
-        dataset.copy_training_input_signal(i, &mut input_signal);
-        run_network_forward_propagation(net, activation_function, &input_signal);
-        dataset.copy_training_output_signal(i, &mut output_signal_reference);
-        on_iteration_ended_hook(&output_signal_reference, net.output_layer());
+        for isample in ibegin..iend {
+            dataset.copy_training_output_signal(isample, &mut output_signal_reference);
+            Error::check_len(output_len, output_signal_reference.len())?;
+            output_signal.copy_from_slice(outputs.row(isample - ibegin));
+            loss_sum += loss.value(&output_signal_reference, &output_signal);
+            on_iteration_ended_hook(&output_signal_reference, &output_signal);
+        }
     }
+
+    Ok(loss_sum / dataset.length().max(1) as f32)
 }
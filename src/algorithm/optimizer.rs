@@ -0,0 +1,690 @@
+//! Gradient based update rules for weights and biases.
+
+use crate::network::{Coeff, Network};
+use crate::error::{Error, Result};
+use super::kernel;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+/// Identifies a group of learnable parameters, so an optimizer can associate
+/// its state with it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Parameter {
+    /// Weights of the edges ending on a layer
+    Weights(usize),
+    /// Biases of the nodes of a layer
+    Biases(usize),
+}
+
+impl Parameter {
+    /// Layer the parameters belong to
+    pub fn ilayer(&self) -> usize {
+        match *self {
+            Parameter::Weights(ilayer) | Parameter::Biases(ilayer) => ilayer,
+        }
+    }
+
+    /// Dense index of a parameter group, suitable for indexing state storage
+    fn islot(&self) -> usize {
+        match *self {
+            Parameter::Weights(ilayer) => 2 * ilayer,
+            Parameter::Biases(ilayer) => 2 * ilayer + 1,
+        }
+    }
+}
+
+/// Update rule for learnable parameters.
+///
+/// An optimizer receives a gradient for each group of parameters once per
+/// step, and owns whatever per-parameter state the rule requires.
+pub trait Optimizer {
+    /// Marks the beginning of an update step, i.e. precedes the updates of all
+    /// the parameter groups w/ the gradients from the same batch
+    fn begin_step(&mut self) {}
+
+    /// Updates `values` given the gradient of the cost by them
+    fn update(&mut self, parameter: Parameter, values: &mut [f32], gradient: &[f32]);
+
+    /// Learning rate of the subsequent steps
+    fn rate(&self) -> f32;
+
+    fn set_rate(&mut self, rate: f32);
+
+    /// Marks the end of a training epoch, w/ the validation loss measured
+    /// after it, if the session is validated
+    fn end_epoch(&mut self, _validation_loss: Option<f32>) {}
+
+    /// Regularization term the optimizer's updates add to the cost of the
+    /// network, see `regularization`
+    fn penalty(&self, _net: &Network) -> f32 {
+        0.0f32
+    }
+
+    /// Snapshot of the hyperparameters and the accumulated state
+    fn export_state(&self) -> OptimizerState;
+
+    /// Restores a snapshot made by the same kind of optimizer for a network
+    /// of `net`'s geometry
+    fn import_state(&mut self, state: &OptimizerState, net: &Network) -> Result<()>;
+}
+
+/// Per-parameter state, one buffer per parameter group. Buffers are allocated
+/// and zeroed on first access.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct State {
+    slots: Vec<Coeff>,
+}
+
+impl State {
+    pub fn slot(&mut self, parameter: Parameter, len: usize) -> &mut Coeff {
+        let islot = parameter.islot();
+
+        if self.slots.len() <= islot {
+            self.slots.resize(islot + 1, Coeff::new());
+        }
+
+        let slot = &mut self.slots[islot];
+
+        if slot.len() != len {
+            slot.clear();
+            slot.resize(len, 0.0f32);
+        }
+
+        slot
+    }
+}
+
+/// Optimizer a snapshot is taken of
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OptimizerKind {
+    Sgd = 0,
+    Momentum = 1,
+    Nesterov = 2,
+    Adagrad = 3,
+    RmsProp = 4,
+    Adam = 5,
+    AdamW = 6,
+}
+
+impl OptimizerKind {
+    /// All the kinds, ordered by id
+    pub const ALL: [OptimizerKind; 7] = [
+        OptimizerKind::Sgd,
+        OptimizerKind::Momentum,
+        OptimizerKind::Nesterov,
+        OptimizerKind::Adagrad,
+        OptimizerKind::RmsProp,
+        OptimizerKind::Adam,
+        OptimizerKind::AdamW,
+    ];
+
+    pub fn from_id(id: usize) -> Option<OptimizerKind> {
+        OptimizerKind::ALL.get(id).copied()
+    }
+}
+
+/// Kinds are stored by their numeric ids
+impl Serialize for OptimizerKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+impl<'de> Deserialize<'de> for OptimizerKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<OptimizerKind, D::Error> {
+        let id = u8::deserialize(deserializer)?;
+
+        OptimizerKind::from_id(id as usize).ok_or_else(|| serde::de::Error::custom("unknown optimizer kind"))
+    }
+}
+
+/// Position of a scheduled optimizer along its learning rate schedule, see
+/// `schedule::Scheduled`
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScheduleState {
+    /// Number of steps taken under the schedule
+    pub step: u64,
+    /// The schedule's own snapshot
+    pub state: Vec<f32>,
+}
+
+/// Everything an optimizer needs to resume where it stopped
+#[derive(Clone, PartialEq, Debug)]
+pub struct OptimizerState {
+    pub kind: OptimizerKind,
+    /// Rate, decays, etc. in the order the optimizer's constructor takes them
+    pub hyperparameters: Vec<f32>,
+    /// Number of steps taken, for the optimizers which track it
+    pub n_steps: u64,
+    pub buffers: Vec<State>,
+    /// Present for scheduled optimizers only
+    pub schedule: Option<ScheduleState>,
+}
+
+impl OptimizerState {
+    fn new(kind: OptimizerKind, hyperparameters: &[f32], n_steps: u64, buffers: &[&State]) -> OptimizerState {
+        OptimizerState{
+            kind,
+            hyperparameters: hyperparameters.to_vec(),
+            n_steps,
+            buffers: buffers.iter().map(|buffer| (*buffer).clone()).collect(),
+            schedule: None,
+        }
+    }
+
+    /// Checks that the snapshot is of a `kind` optimizer w/ `N`
+    /// hyperparameters and `n_buffers` buffers fitting `net`, and returns the
+    /// hyperparameters
+    fn unpack<const N: usize>(&self, kind: OptimizerKind, n_buffers: usize, net: &Network)
+        -> Result<[f32; N]>
+    {
+        if self.kind != kind {
+            return Err(Error::OptimizerStateMismatch(format!("expected a {:?} snapshot, got a {:?} one",
+                kind, self.kind)));
+        }
+
+        if self.schedule.is_some() {
+            return Err(Error::OptimizerStateMismatch("unexpected schedule state".into()));
+        }
+
+        if self.buffers.len() != n_buffers {
+            return Err(Error::OptimizerStateMismatch(format!("expected {} buffers, got {}",
+                n_buffers, self.buffers.len())));
+        }
+
+        self.check_buffers(net)?;
+
+        self.hyperparameters.as_slice().try_into()
+            .map_err(|_| Error::OptimizerStateMismatch(format!("expected {} hyperparameters, got {}",
+                N, self.hyperparameters.len())))
+    }
+
+    /// Checks that each slot of the buffers is as long as the parameter group
+    /// of `net` it belongs to. Slots of the groups never updated are empty.
+    fn check_buffers(&self, net: &Network) -> Result<()> {
+        for buffer in &self.buffers {
+            for (islot, slot) in buffer.slots.iter().enumerate().filter(|(_, slot)| !slot.is_empty()) {
+                let ilayer = islot / 2;
+                let len = if ilayer == 0 || ilayer >= net.n_layers() {
+                    0
+                } else if islot % 2 == 0 {
+                    net.w_matrix(ilayer).as_slice().len()
+                } else {
+                    net.b_vec(ilayer).len()
+                };
+
+                if slot.len() != len {
+                    return Err(Error::OptimizerStateMismatch(format!("slot {} of {} values, expected {}",
+                        islot, slot.len(), len)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Serialize for OptimizerState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let buffers: Vec<&Vec<Coeff>> = self.buffers.iter().map(|buffer| &buffer.slots).collect();
+
+        let schedule = self.schedule.as_ref().map(|schedule| (schedule.step, &schedule.state));
+
+        (self.kind, &self.hyperparameters, self.n_steps, buffers, schedule).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for OptimizerState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<OptimizerState, D::Error> {
+        type StateTuple = (OptimizerKind, Vec<f32>, u64, Vec<Vec<Coeff>>, Option<(u64, Vec<f32>)>);
+        let (kind, hyperparameters, n_steps, buffers, schedule) = StateTuple::deserialize(deserializer)?;
+
+        Ok(OptimizerState{
+            kind,
+            hyperparameters,
+            n_steps,
+            buffers: buffers.into_iter().map(|slots| State{slots}).collect(),
+            schedule: schedule.map(|(step, state)| ScheduleState{step, state}),
+        })
+    }
+}
+
+/// Plain stochastic gradient descent: `w = w - rate * dc/dw`
+pub struct Sgd {
+    pub rate: f32,
+}
+
+impl Sgd {
+    pub fn new(rate: f32) -> Sgd {
+        Sgd{rate}
+    }
+}
+
+impl Optimizer for Sgd {
+    fn update(&mut self, _parameter: Parameter, values: &mut [f32], gradient: &[f32]) {
+        kernel::axpy(-self.rate, gradient, values);
+    }
+
+    fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    fn set_rate(&mut self, rate: f32) {
+        self.rate = rate;
+    }
+
+    fn export_state(&self) -> OptimizerState {
+        OptimizerState::new(OptimizerKind::Sgd, &[self.rate], 0, &[])
+    }
+
+    fn import_state(&mut self, state: &OptimizerState, net: &Network) -> Result<()> {
+        [self.rate] = state.unpack(OptimizerKind::Sgd, 0, net)?;
+
+        Ok(())
+    }
+}
+
+/// SGD w/ momentum (heavy ball):
+/// `v = momentum * v - rate * dc/dw; w = w + v`
+pub struct Momentum {
+    pub rate: f32,
+    pub momentum: f32,
+    velocity: State,
+}
+
+impl Momentum {
+    pub fn new(rate: f32, momentum: f32) -> Momentum {
+        Momentum{rate, momentum, velocity: State::default()}
+    }
+}
+
+impl Optimizer for Momentum {
+    fn update(&mut self, parameter: Parameter, values: &mut [f32], gradient: &[f32]) {
+        let velocity = self.velocity.slot(parameter, values.len());
+
+        for ((value, g), v) in values.iter_mut().zip(gradient).zip(velocity.iter_mut()) {
+            *v = self.momentum * *v - self.rate * g;
+            *value += *v;
+        }
+    }
+
+    fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    fn set_rate(&mut self, rate: f32) {
+        self.rate = rate;
+    }
+
+    fn export_state(&self) -> OptimizerState {
+        OptimizerState::new(OptimizerKind::Momentum, &[self.rate, self.momentum], 0, &[&self.velocity])
+    }
+
+    fn import_state(&mut self, state: &OptimizerState, net: &Network) -> Result<()> {
+        [self.rate, self.momentum] = state.unpack(OptimizerKind::Momentum, 1, net)?;
+        self.velocity = state.buffers[0].clone();
+
+        Ok(())
+    }
+}
+
+/// Nesterov accelerated gradient, in the formulation which does not require
+/// evaluating the gradient at the look-ahead point:
+/// `v' = momentum * v - rate * dc/dw; w = w - momentum * v + (1 + momentum) * v'`
+pub struct Nesterov {
+    pub rate: f32,
+    pub momentum: f32,
+    velocity: State,
+}
+
+impl Nesterov {
+    pub fn new(rate: f32, momentum: f32) -> Nesterov {
+        Nesterov{rate, momentum, velocity: State::default()}
+    }
+}
+
+impl Optimizer for Nesterov {
+    fn update(&mut self, parameter: Parameter, values: &mut [f32], gradient: &[f32]) {
+        let velocity = self.velocity.slot(parameter, values.len());
+
+        for ((value, g), v) in values.iter_mut().zip(gradient).zip(velocity.iter_mut()) {
+            let v_prev = *v;
+            *v = self.momentum * *v - self.rate * g;
+            *value += -self.momentum * v_prev + (1.0 + self.momentum) * *v;
+        }
+    }
+
+    fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    fn set_rate(&mut self, rate: f32) {
+        self.rate = rate;
+    }
+
+    fn export_state(&self) -> OptimizerState {
+        OptimizerState::new(OptimizerKind::Nesterov, &[self.rate, self.momentum], 0, &[&self.velocity])
+    }
+
+    fn import_state(&mut self, state: &OptimizerState, net: &Network) -> Result<()> {
+        [self.rate, self.momentum] = state.unpack(OptimizerKind::Nesterov, 1, net)?;
+        self.velocity = state.buffers[0].clone();
+
+        Ok(())
+    }
+}
+
+/// Adagrad: per-parameter rates shrinking w/ the accumulated squared gradients
+pub struct Adagrad {
+    pub rate: f32,
+    pub epsilon: f32,
+    sum_squared: State,
+}
+
+impl Adagrad {
+    pub fn new(rate: f32, epsilon: f32) -> Adagrad {
+        Adagrad{rate, epsilon, sum_squared: State::default()}
+    }
+}
+
+impl Optimizer for Adagrad {
+    fn update(&mut self, parameter: Parameter, values: &mut [f32], gradient: &[f32]) {
+        let sum_squared = self.sum_squared.slot(parameter, values.len());
+
+        for ((value, g), s) in values.iter_mut().zip(gradient).zip(sum_squared.iter_mut()) {
+            *s += g * g;
+            *value -= self.rate * g / (s.sqrt() + self.epsilon);
+        }
+    }
+
+    fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    fn set_rate(&mut self, rate: f32) {
+        self.rate = rate;
+    }
+
+    fn export_state(&self) -> OptimizerState {
+        OptimizerState::new(OptimizerKind::Adagrad, &[self.rate, self.epsilon], 0, &[&self.sum_squared])
+    }
+
+    fn import_state(&mut self, state: &OptimizerState, net: &Network) -> Result<()> {
+        [self.rate, self.epsilon] = state.unpack(OptimizerKind::Adagrad, 1, net)?;
+        self.sum_squared = state.buffers[0].clone();
+
+        Ok(())
+    }
+}
+
+/// RMSProp: per-parameter rates normalized by the running mean of squared
+/// gradients
+pub struct RmsProp {
+    pub rate: f32,
+    /// Decay of the running mean
+    pub rho: f32,
+    pub epsilon: f32,
+    mean_squared: State,
+}
+
+impl RmsProp {
+    pub fn new(rate: f32, rho: f32, epsilon: f32) -> RmsProp {
+        RmsProp{rate, rho, epsilon, mean_squared: State::default()}
+    }
+}
+
+impl Optimizer for RmsProp {
+    fn update(&mut self, parameter: Parameter, values: &mut [f32], gradient: &[f32]) {
+        let mean_squared = self.mean_squared.slot(parameter, values.len());
+
+        for ((value, g), s) in values.iter_mut().zip(gradient).zip(mean_squared.iter_mut()) {
+            *s = self.rho * *s + (1.0 - self.rho) * g * g;
+            *value -= self.rate * g / (s.sqrt() + self.epsilon);
+        }
+    }
+
+    fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    fn set_rate(&mut self, rate: f32) {
+        self.rate = rate;
+    }
+
+    fn export_state(&self) -> OptimizerState {
+        OptimizerState::new(OptimizerKind::RmsProp, &[self.rate, self.rho, self.epsilon], 0,
+            &[&self.mean_squared])
+    }
+
+    fn import_state(&mut self, state: &OptimizerState, net: &Network) -> Result<()> {
+        [self.rate, self.rho, self.epsilon] = state.unpack(OptimizerKind::RmsProp, 1, net)?;
+        self.mean_squared = state.buffers[0].clone();
+
+        Ok(())
+    }
+}
+
+/// Adam: bias-corrected running means of gradients and squared gradients
+pub struct Adam {
+    pub rate: f32,
+    pub beta1: f32,
+    pub beta2: f32,
+    pub epsilon: f32,
+    /// Number of steps taken
+    t: i32,
+    m: State,
+    v: State,
+}
+
+impl Adam {
+    pub fn new(rate: f32, beta1: f32, beta2: f32, epsilon: f32) -> Adam {
+        Adam{rate, beta1, beta2, epsilon, t: 0, m: State::default(), v: State::default()}
+    }
+
+    /// The hyperparameters suggested by the authors of the method
+    pub fn with_rate(rate: f32) -> Adam {
+        Adam::new(rate, 0.9, 0.999, 1e-8)
+    }
+}
+
+impl Optimizer for Adam {
+    fn begin_step(&mut self) {
+        self.t += 1;
+    }
+
+    fn update(&mut self, parameter: Parameter, values: &mut [f32], gradient: &[f32]) {
+        // Makes `update` usable w/o `begin_step`, e.g. for a single parameter group
+        let t = self.t.max(1);
+        let correction1 = 1.0 - self.beta1.powi(t);
+        let correction2 = 1.0 - self.beta2.powi(t);
+        let m = self.m.slot(parameter, values.len());
+        let v = self.v.slot(parameter, values.len());
+
+        for (((value, g), m), v) in values.iter_mut().zip(gradient).zip(m.iter_mut()).zip(v.iter_mut()) {
+            *m = self.beta1 * *m + (1.0 - self.beta1) * g;
+            *v = self.beta2 * *v + (1.0 - self.beta2) * g * g;
+            let m_hat = *m / correction1;
+            let v_hat = *v / correction2;
+            *value -= self.rate * m_hat / (v_hat.sqrt() + self.epsilon);
+        }
+    }
+
+    fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    fn set_rate(&mut self, rate: f32) {
+        self.rate = rate;
+    }
+
+    fn export_state(&self) -> OptimizerState {
+        OptimizerState::new(OptimizerKind::Adam, &[self.rate, self.beta1, self.beta2, self.epsilon],
+            self.t as u64, &[&self.m, &self.v])
+    }
+
+    fn import_state(&mut self, state: &OptimizerState, net: &Network) -> Result<()> {
+        [self.rate, self.beta1, self.beta2, self.epsilon] = state.unpack(OptimizerKind::Adam, 2, net)?;
+        self.t = state.n_steps.try_into()
+            .map_err(|_| Error::OptimizerStateMismatch(format!("{} steps", state.n_steps)))?;
+        self.m = state.buffers[0].clone();
+        self.v = state.buffers[1].clone();
+
+        Ok(())
+    }
+}
+
+/// Adam w/ decoupled weight decay. Weights are shrunk directly instead of
+/// having the decay term go through the adaptive moments. Biases are not
+/// decayed.
+pub struct AdamW {
+    pub adam: Adam,
+    pub weight_decay: f32,
+}
+
+impl AdamW {
+    pub fn new(adam: Adam, weight_decay: f32) -> AdamW {
+        AdamW{adam, weight_decay}
+    }
+}
+
+impl Optimizer for AdamW {
+    fn begin_step(&mut self) {
+        self.adam.begin_step();
+    }
+
+    fn update(&mut self, parameter: Parameter, values: &mut [f32], gradient: &[f32]) {
+        if let Parameter::Weights(_) = parameter {
+            let decay = self.adam.rate * self.weight_decay;
+            values.iter_mut().for_each(|value| *value -= decay * *value);
+        }
+
+        self.adam.update(parameter, values, gradient);
+    }
+
+    fn rate(&self) -> f32 {
+        self.adam.rate
+    }
+
+    fn set_rate(&mut self, rate: f32) {
+        self.adam.rate = rate;
+    }
+
+    /// Adam's snapshot w/ the weight decay appended to the hyperparameters
+    fn export_state(&self) -> OptimizerState {
+        let mut state = self.adam.export_state();
+        state.kind = OptimizerKind::AdamW;
+        state.hyperparameters.push(self.weight_decay);
+
+        state
+    }
+
+    fn import_state(&mut self, state: &OptimizerState, net: &Network) -> Result<()> {
+        if state.kind != OptimizerKind::AdamW {
+            return Err(Error::OptimizerStateMismatch(format!("expected a {:?} snapshot, got a {:?} one",
+                OptimizerKind::AdamW, state.kind)));
+        }
+
+        let mut adam_state = state.clone();
+        adam_state.kind = OptimizerKind::Adam;
+        self.weight_decay = adam_state.hyperparameters.pop()
+            .ok_or_else(|| Error::OptimizerStateMismatch("missing weight decay".into()))?;
+
+        self.adam.import_state(&adam_state, net)
+    }
+}
+
+#[cfg(test)]
+mod test_optimizer {
+    use super::*;
+
+    /// Network whose weights are the parameters of `minimize`
+    fn network() -> Network {
+        Network::from_geometry(&vec![1, 2])
+    }
+
+    /// Minimizes `(x - 3)^2` for each of the parameters
+    fn minimize(optimizer: &mut dyn Optimizer, n_steps: usize) -> Vec<f32> {
+        let mut values = vec![0.0f32, 10.0f32];
+        let mut gradient = vec![0.0f32; values.len()];
+
+        for _ in 0..n_steps {
+            for (g, x) in gradient.iter_mut().zip(values.iter()) {
+                *g = 2.0 * (x - 3.0);
+            }
+
+            optimizer.begin_step();
+            optimizer.update(Parameter::Weights(1), &mut values, &gradient);
+        }
+
+        values
+    }
+
+    #[test]
+    fn convergence() {
+        let optimizers: Vec<Box<dyn Optimizer>> = vec![
+            Box::new(Sgd::new(0.1)),
+            Box::new(Momentum::new(0.05, 0.9)),
+            Box::new(Nesterov::new(0.05, 0.9)),
+            Box::new(Adagrad::new(1.0, 1e-8)),
+            Box::new(RmsProp::new(0.01, 0.9, 1e-8)),
+            Box::new(Adam::with_rate(0.1)),
+            Box::new(AdamW::new(Adam::with_rate(0.1), 0.0)),
+        ];
+
+        for mut optimizer in optimizers {
+            for x in minimize(optimizer.as_mut(), 2000) {
+                assert!((x - 3.0).abs() < 1e-2, "{}", x);
+            }
+        }
+    }
+
+    #[test]
+    fn adamw_decays_weights_only() {
+        let mut optimizer = AdamW::new(Adam::with_rate(0.1), 0.5);
+        let mut weights = vec![1.0f32];
+        let mut biases = vec![1.0f32];
+        optimizer.begin_step();
+        optimizer.update(Parameter::Weights(1), &mut weights, &[0.0]);
+        optimizer.update(Parameter::Biases(1), &mut biases, &[0.0]);
+
+        assert!((weights[0] - 0.95).abs() < 1e-6);
+        assert_eq!(biases[0], 1.0);
+    }
+
+    /// An optimizer restored from a snapshot must continue exactly as the
+    /// original one
+    #[test]
+    fn state_round_trip() {
+        let mut original = AdamW::new(Adam::with_rate(0.1), 0.01);
+        minimize(&mut original, 10);
+        let serialized = bincode::serialize(&original.export_state()).unwrap();
+        let mut restored = AdamW::new(Adam::with_rate(1.0), 0.0);
+        restored.import_state(&bincode::deserialize(&serialized).unwrap(), &network()).unwrap();
+        assert_eq!(restored.export_state(), original.export_state());
+        assert!(Sgd::new(0.1).import_state(&original.export_state(), &network()).is_err());
+        assert!(Adam::with_rate(0.1).import_state(&original.export_state(), &network()).is_err());
+    }
+
+    /// Buffers must fit the parameters of the network the state is imported for
+    #[test]
+    fn state_geometry_mismatch() {
+        let mut original = Momentum::new(0.1, 0.9);
+        minimize(&mut original, 1);
+        let mut restored = Momentum::new(0.1, 0.9);
+
+        for geometry in [vec![2, 2], vec![1]] {
+            let network = Network::from_geometry(&geometry);
+            assert!(matches!(restored.import_state(&original.export_state(), &network),
+                Err(Error::OptimizerStateMismatch(_))), "{:?}", geometry);
+        }
+    }
+
+    /// Optimizers w/ the same hyperparameters and buffers must not accept
+    /// each other's snapshots
+    #[test]
+    fn state_kind_mismatch() {
+        let momentum = Momentum::new(0.1, 0.9).export_state();
+        assert!(Nesterov::new(0.1, 0.9).import_state(&momentum, &network()).is_err());
+        assert!(Adagrad::new(0.1, 1e-8).import_state(&momentum, &network()).is_err());
+        assert!(Momentum::new(1.0, 0.0).import_state(&momentum, &network()).is_ok());
+    }
+}
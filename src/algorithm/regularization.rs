@@ -0,0 +1,191 @@
+//! Regularization of weights and biases.
+//!
+//! L1 and L2 add `l1 * sum(|w|) + l2 / 2 * sum(w^2)` to the cost, and their
+//! derivatives to the gradient. Decoupled weight decay shrinks the
+//! parameters by `rate * weight_decay * w` each step, bypassing the
+//! optimizer's rule, and is not a part of the cost.
+
+use super::optimizer::{Optimizer, OptimizerState, Parameter};
+use crate::network::Network;
+use crate::error::Result;
+
+/// Regularization of a layer's parameters
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Regularization {
+    pub l1: f32,
+    pub l2: f32,
+    pub weight_decay: f32,
+    /// Whether biases are regularized along w/ the weights
+    pub is_bias_included: bool,
+}
+
+impl Regularization {
+    fn is_applied_to(&self, parameter: Parameter) -> bool {
+        matches!(parameter, Parameter::Weights(_)) || self.is_bias_included
+    }
+
+    /// Penalty of a group of parameters
+    fn penalty(&self, values: &[f32]) -> f32 {
+        let l1 = if self.l1 != 0.0 { self.l1 * values.iter().map(|v| v.abs()).sum::<f32>() } else { 0.0 };
+        let l2 = if self.l2 != 0.0 { 0.5 * self.l2 * values.iter().map(|v| v * v).sum::<f32>() } else { 0.0 };
+
+        l1 + l2
+    }
+}
+
+/// An optimizer minimizing the cost w/ the regularization terms added.
+/// Regularization of each layer is the default one, unless overridden.
+pub struct Regularized<O: Optimizer> {
+    pub optimizer: O,
+    pub default: Regularization,
+    /// Overrides, indexed by layer
+    layers: Vec<Option<Regularization>>,
+    /// Gradient w/ the penalty's derivative added
+    gradient: Vec<f32>,
+}
+
+impl<O: Optimizer> Regularized<O> {
+    pub fn new(optimizer: O, default: Regularization) -> Regularized<O> {
+        Regularized{optimizer, default, layers: Vec::new(), gradient: Vec::new()}
+    }
+
+    /// Overrides regularization of a layer
+    pub fn set_layer(&mut self, ilayer: usize, regularization: Regularization) {
+        if self.layers.len() <= ilayer {
+            self.layers.resize(ilayer + 1, None);
+        }
+
+        self.layers[ilayer] = Some(regularization);
+    }
+
+    pub fn layer(&self, ilayer: usize) -> &Regularization {
+        self.layers.get(ilayer).and_then(Option::as_ref).unwrap_or(&self.default)
+    }
+}
+
+impl<O: Optimizer> Optimizer for Regularized<O> {
+    fn begin_step(&mut self) {
+        self.optimizer.begin_step();
+    }
+
+    fn update(&mut self, parameter: Parameter, values: &mut [f32], gradient: &[f32]) {
+        let regularization = *self.layer(parameter.ilayer());
+
+        if !regularization.is_applied_to(parameter) {
+            self.optimizer.update(parameter, values, gradient);
+
+            return;
+        }
+
+        // d|w|/dw is taken as 0 at w = 0, so L1 does not make weights oscillate around it
+        self.gradient.clear();
+        self.gradient.extend(gradient.iter().zip(values.iter()).map(|(g, w)| {
+            let sign = if *w > 0.0 { 1.0 } else if *w < 0.0 { -1.0 } else { 0.0 };
+            g + regularization.l1 * sign + regularization.l2 * w
+        }));
+        let decay = self.optimizer.rate() * regularization.weight_decay;
+
+        if decay != 0.0 {
+            values.iter_mut().for_each(|value| *value -= decay * *value);
+        }
+
+        self.optimizer.update(parameter, values, &self.gradient);
+    }
+
+    fn rate(&self) -> f32 {
+        self.optimizer.rate()
+    }
+
+    fn set_rate(&mut self, rate: f32) {
+        self.optimizer.set_rate(rate);
+    }
+
+    fn end_epoch(&mut self, validation_loss: Option<f32>) {
+        self.optimizer.end_epoch(validation_loss);
+    }
+
+    fn penalty(&self, net: &Network) -> f32 {
+        let mut penalty = self.optimizer.penalty(net);
+
+        for ilayer in 1..net.n_layers() {
+            let regularization = self.layer(ilayer);
+            penalty += regularization.penalty(net.w_matrix(ilayer).as_slice());
+
+            if regularization.is_bias_included {
+                penalty += regularization.penalty(net.b_vec(ilayer));
+            }
+        }
+
+        penalty
+    }
+
+    /// The optimizer's snapshot. Regularization is configuration, and is not
+    /// a part of it.
+    fn export_state(&self) -> OptimizerState {
+        self.optimizer.export_state()
+    }
+
+    fn import_state(&mut self, state: &OptimizerState, net: &Network) -> Result<()> {
+        self.optimizer.import_state(state, net)
+    }
+}
+
+#[cfg(test)]
+mod test_regularization {
+    use super::*;
+    use crate::algorithm::optimizer::Sgd;
+
+    /// W/ a zero data gradient, a step of plain gradient descent must follow
+    /// the derivative of the penalty
+    #[test]
+    fn gradient_matches_penalty() {
+        const H: f32 = 1e-3;
+        let mut net = Network::from_geometry(&vec![2, 2]);
+        net.w_matrix_mut(1).as_mut_slice().copy_from_slice(&[0.5, -1.0, 2.0, 0.0]);
+        net.b_vec_mut(1).copy_from_slice(&[1.0, -1.0]);
+        let mut optimizer = Regularized::new(Sgd::new(1.0),
+            Regularization{l1: 0.1, l2: 0.2, weight_decay: 0.0, is_bias_included: true});
+        let penalty = optimizer.penalty(&net);
+        assert!((penalty - (0.1 * 5.5 + 0.1 * 7.25)).abs() < 1e-6, "{}", penalty);
+
+        for parameter in [Parameter::Weights(1), Parameter::Biases(1)] {
+            let values: Vec<f32> = match parameter {
+                Parameter::Weights(_) => net.w_matrix(1).as_slice().to_vec(),
+                Parameter::Biases(_) => net.b_vec(1).clone(),
+            };
+            let mut updated = values.clone();
+            optimizer.update(parameter, &mut updated, &vec![0.0; values.len()]);
+
+            for (i, value) in values.iter().enumerate().filter(|(_, value)| **value != 0.0) {
+                let mut probe = net.clone();
+                let mut penalty_at = |value: f32| {
+                    match parameter {
+                        Parameter::Weights(_) => probe.w_matrix_mut(1).as_mut_slice()[i] = value,
+                        Parameter::Biases(_) => probe.b_vec_mut(1)[i] = value,
+                    }
+
+                    optimizer.penalty(&probe)
+                };
+                let numeric = (penalty_at(value + H) - penalty_at(value - H)) / (2.0 * H);
+                assert!((value - updated[i] - numeric).abs() < 1e-2, "{:?}[{}]", parameter, i);
+            }
+        }
+    }
+
+    #[test]
+    fn per_layer() {
+        let mut optimizer = Regularized::new(Sgd::new(0.5),
+            Regularization{weight_decay: 0.1, ..Regularization::default()});
+        optimizer.set_layer(2, Regularization::default());
+        let mut weights = vec![1.0f32];
+        let mut biases = vec![1.0f32];
+        optimizer.update(Parameter::Weights(1), &mut weights, &[0.0]);
+        optimizer.update(Parameter::Biases(1), &mut biases, &[0.0]);
+        assert!((weights[0] - 0.95).abs() < 1e-6);
+        assert_eq!(biases[0], 1.0);
+
+        optimizer.update(Parameter::Weights(2), &mut weights, &[0.0]);
+        assert!((weights[0] - 0.95).abs() < 1e-6);
+        assert_eq!(optimizer.penalty(&Network::from_geometry(&vec![1, 1, 1])), 0.0);
+    }
+}
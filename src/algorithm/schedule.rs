@@ -0,0 +1,350 @@
+//! Learning rate schedules.
+//!
+//! A schedule maps the number of update steps taken so far onto a learning
+//! rate. `Scheduled` wraps an optimizer, and sets its rate before each step,
+//! so the training routines need not know about schedules.
+
+use super::optimizer::{Optimizer, OptimizerState, Parameter, ScheduleState};
+use crate::network::Network;
+use crate::error::{Error, Result};
+use std::f32::consts::PI;
+
+pub trait Schedule {
+    /// Rate of the update step `step`, counting from 0
+    fn rate(&self, step: u64) -> f32;
+
+    /// Marks the end of a training epoch, see `Optimizer::end_epoch`
+    fn end_epoch(&mut self, _validation_loss: Option<f32>) {}
+
+    /// State accumulated during training, for schedules which have one
+    fn export_state(&self) -> Vec<f32> {
+        Vec::new()
+    }
+
+    /// Restores a snapshot made by `export_state` of the same kind of schedule
+    fn import_state(&mut self, state: &[f32]) -> Result<()> {
+        Error::check_len(0, state.len())
+    }
+}
+
+/// Rate multiplied by `gamma` once per `step_size` steps
+#[derive(Clone, Debug)]
+pub struct StepDecay {
+    pub rate: f32,
+    pub gamma: f32,
+    pub step_size: u64,
+}
+
+impl Schedule for StepDecay {
+    fn rate(&self, step: u64) -> f32 {
+        self.rate * self.gamma.powf((step / self.step_size.max(1)) as f32)
+    }
+}
+
+/// Rate multiplied by `gamma` each step
+#[derive(Clone, Debug)]
+pub struct ExponentialDecay {
+    pub rate: f32,
+    pub gamma: f32,
+}
+
+impl Schedule for ExponentialDecay {
+    fn rate(&self, step: u64) -> f32 {
+        self.rate * self.gamma.powf(step as f32)
+    }
+}
+
+/// Loshchilov & Hutter, SGDR. Within each cycle, the rate is annealed from
+/// `rate_max` down to `rate_min` along a half cosine. The first cycle is
+/// `period` steps long, each next one is `period_mult` times longer.
+#[derive(Clone, Debug)]
+pub struct CosineWarmRestarts {
+    pub rate_max: f32,
+    pub rate_min: f32,
+    pub period: u64,
+    pub period_mult: u64,
+}
+
+impl Schedule for CosineWarmRestarts {
+    fn rate(&self, step: u64) -> f32 {
+        let mut period = self.period.max(1);
+        let mut t = step;
+
+        if self.period_mult <= 1 {
+            t %= period;
+        } else {
+            while t >= period {
+                t -= period;
+                period *= self.period_mult;
+            }
+        }
+
+        cosine_anneal(self.rate_max, self.rate_min, t as f32 / period as f32)
+    }
+}
+
+/// Rate growing linearly from `rate / n_steps` to the rate of the wrapped
+/// schedule over the first `n_steps` steps. The wrapped schedule starts
+/// afterwards.
+#[derive(Clone, Debug)]
+pub struct LinearWarmup<S: Schedule> {
+    pub n_steps: u64,
+    pub schedule: S,
+}
+
+impl<S: Schedule> Schedule for LinearWarmup<S> {
+    fn rate(&self, step: u64) -> f32 {
+        if step < self.n_steps {
+            self.schedule.rate(0) * (step + 1) as f32 / self.n_steps as f32
+        } else {
+            self.schedule.rate(step - self.n_steps)
+        }
+    }
+
+    fn end_epoch(&mut self, validation_loss: Option<f32>) {
+        self.schedule.end_epoch(validation_loss);
+    }
+
+    fn export_state(&self) -> Vec<f32> {
+        self.schedule.export_state()
+    }
+
+    fn import_state(&mut self, state: &[f32]) -> Result<()> {
+        self.schedule.import_state(state)
+    }
+}
+
+/// Smith & Topin, the 1cycle policy. The rate is annealed along half cosines
+/// from `rate_max / div_factor` up to `rate_max` over the first
+/// `warmup_fraction` of `n_steps`, and then down to
+/// `rate_max / div_factor / final_div_factor`, where it stays.
+#[derive(Clone, Debug)]
+pub struct OneCycle {
+    pub rate_max: f32,
+    pub n_steps: u64,
+    pub warmup_fraction: f32,
+    pub div_factor: f32,
+    pub final_div_factor: f32,
+}
+
+impl OneCycle {
+    /// Warm-up over 30% of the steps, initial rate being 1/25 of the peak,
+    /// and the final one 1/10^4 of the initial
+    pub fn new(rate_max: f32, n_steps: u64) -> OneCycle {
+        OneCycle{rate_max, n_steps, warmup_fraction: 0.3, div_factor: 25.0, final_div_factor: 1e4}
+    }
+}
+
+impl Schedule for OneCycle {
+    fn rate(&self, step: u64) -> f32 {
+        let rate_initial = self.rate_max / self.div_factor;
+        let rate_final = rate_initial / self.final_div_factor;
+        let n_steps_up = (self.warmup_fraction * self.n_steps as f32) as u64;
+
+        if step < n_steps_up {
+            cosine_anneal(rate_initial, self.rate_max, step as f32 / n_steps_up as f32)
+        } else if step < self.n_steps {
+            cosine_anneal(self.rate_max, rate_final,
+                (step - n_steps_up) as f32 / (self.n_steps - n_steps_up) as f32)
+        } else {
+            rate_final
+        }
+    }
+}
+
+/// Rate multiplied by `factor` once the validation loss has not improved by
+/// `min_delta` for `patience` epochs in a row, but not below `rate_min`.
+/// Epochs w/o validation do not count. Patience is counted as by
+/// `EarlyStopping`, so w/ the same value the rate is reduced at the epoch the
+/// session would be stopped.
+#[derive(Clone, Debug)]
+pub struct ReduceOnPlateau {
+    pub factor: f32,
+    pub patience: usize,
+    pub min_delta: f32,
+    pub rate_min: f32,
+    rate: f32,
+    loss_best: f32,
+    n_epochs_stale: usize,
+}
+
+impl ReduceOnPlateau {
+    pub fn new(rate: f32, factor: f32, patience: usize, min_delta: f32, rate_min: f32) -> ReduceOnPlateau {
+        ReduceOnPlateau{factor, patience, min_delta, rate_min, rate, loss_best: f32::INFINITY, n_epochs_stale: 0}
+    }
+}
+
+impl Schedule for ReduceOnPlateau {
+    fn rate(&self, _step: u64) -> f32 {
+        self.rate
+    }
+
+    fn end_epoch(&mut self, validation_loss: Option<f32>) {
+        let Some(validation_loss) = validation_loss else {
+            return;
+        };
+
+        if validation_loss < self.loss_best - self.min_delta {
+            self.loss_best = validation_loss;
+            self.n_epochs_stale = 0;
+        } else {
+            self.n_epochs_stale += 1;
+
+            if self.n_epochs_stale >= self.patience {
+                self.rate = (self.rate * self.factor).max(self.rate_min);
+                self.n_epochs_stale = 0;
+            }
+        }
+    }
+
+    fn export_state(&self) -> Vec<f32> {
+        vec![self.rate, self.loss_best, self.n_epochs_stale as f32]
+    }
+
+    fn import_state(&mut self, state: &[f32]) -> Result<()> {
+        Error::check_len(3, state.len())?;
+        self.rate = state[0];
+        self.loss_best = state[1];
+        self.n_epochs_stale = state[2] as usize;
+
+        Ok(())
+    }
+}
+
+/// Half cosine from `from` at `t` = 0 to `to` at `t` = 1
+fn cosine_anneal(from: f32, to: f32, t: f32) -> f32 {
+    to + 0.5 * (from - to) * (1.0 + (PI * t).cos())
+}
+
+/// An optimizer whose rate is set by a schedule before each step
+pub struct Scheduled<O: Optimizer, S: Schedule> {
+    pub optimizer: O,
+    pub schedule: S,
+    /// Number of steps taken
+    step: u64,
+}
+
+impl<O: Optimizer, S: Schedule> Scheduled<O, S> {
+    pub fn new(optimizer: O, schedule: S) -> Scheduled<O, S> {
+        Scheduled{optimizer, schedule, step: 0}
+    }
+
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+}
+
+impl<O: Optimizer, S: Schedule> Optimizer for Scheduled<O, S> {
+    fn begin_step(&mut self) {
+        self.optimizer.set_rate(self.schedule.rate(self.step));
+        self.step += 1;
+        self.optimizer.begin_step();
+    }
+
+    fn update(&mut self, parameter: Parameter, values: &mut [f32], gradient: &[f32]) {
+        self.optimizer.update(parameter, values, gradient);
+    }
+
+    fn rate(&self) -> f32 {
+        self.optimizer.rate()
+    }
+
+    /// Overridden by the schedule on the next step
+    fn set_rate(&mut self, rate: f32) {
+        self.optimizer.set_rate(rate);
+    }
+
+    fn end_epoch(&mut self, validation_loss: Option<f32>) {
+        self.schedule.end_epoch(validation_loss);
+        self.optimizer.end_epoch(validation_loss);
+    }
+
+    fn penalty(&self, net: &Network) -> f32 {
+        self.optimizer.penalty(net)
+    }
+
+    /// The optimizer's snapshot w/ the schedule's state, and the number of
+    /// steps
+    fn export_state(&self) -> OptimizerState {
+        let mut state = self.optimizer.export_state();
+        state.schedule = Some(ScheduleState{step: self.step, state: self.schedule.export_state()});
+
+        state
+    }
+
+    fn import_state(&mut self, state: &OptimizerState, net: &Network) -> Result<()> {
+        let mut optimizer_state = state.clone();
+        let schedule = optimizer_state.schedule.take()
+            .ok_or_else(|| Error::OptimizerStateMismatch("missing schedule state".into()))?;
+        self.optimizer.import_state(&optimizer_state, net)?;
+        self.schedule.import_state(&schedule.state)?;
+        self.step = schedule.step;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_schedule {
+    use super::*;
+    use crate::algorithm::optimizer::{Sgd, Adam};
+
+    #[test]
+    fn rates() {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-6 * (1.0 + b.abs());
+        let step = StepDecay{rate: 1.0, gamma: 0.5, step_size: 10};
+        assert!(close(step.rate(9), 1.0) && close(step.rate(10), 0.5) && close(step.rate(25), 0.25));
+        assert!(close(ExponentialDecay{rate: 2.0, gamma: 0.9}.rate(2), 1.62));
+
+        let cosine = CosineWarmRestarts{rate_max: 1.0, rate_min: 0.0, period: 10, period_mult: 2};
+        assert!(close(cosine.rate(0), 1.0) && close(cosine.rate(5), 0.5));
+        // The second cycle spans steps 10 to 29
+        assert!(close(cosine.rate(10), 1.0) && close(cosine.rate(20), 0.5) && close(cosine.rate(30), 1.0));
+
+        let warmup = LinearWarmup{n_steps: 4, schedule: step};
+        assert!(close(warmup.rate(0), 0.25) && close(warmup.rate(3), 1.0) && close(warmup.rate(14), 0.5));
+
+        let one_cycle = OneCycle::new(1.0, 100);
+        assert!(close(one_cycle.rate(0), 0.04) && close(one_cycle.rate(30), 1.0));
+        assert!(one_cycle.rate(99) < one_cycle.rate(0) && close(one_cycle.rate(1000), 4e-6));
+    }
+
+    #[test]
+    fn reduce_on_plateau() {
+        let mut optimizer = Scheduled::new(Sgd::new(0.0), ReduceOnPlateau::new(1.0, 0.5, 2, 0.0, 0.3));
+
+        for (loss, rate) in [(1.0, 1.0), (0.9, 1.0), (0.95, 1.0), (0.9, 0.5), (0.8, 0.5), (0.8, 0.5),
+            (0.8, 0.3), (0.8, 0.3), (0.8, 0.3)]
+        {
+            optimizer.end_epoch(Some(loss));
+            optimizer.end_epoch(None);
+            optimizer.begin_step();
+            assert_eq!(optimizer.rate(), rate, "{}", loss);
+        }
+    }
+
+    /// A scheduled optimizer restored from a snapshot must continue at the
+    /// same step w/ the same schedule state
+    #[test]
+    fn state_round_trip() {
+        let mut original = Scheduled::new(Adam::with_rate(0.0),
+            LinearWarmup{n_steps: 3, schedule: ReduceOnPlateau::new(0.1, 0.5, 0, 0.0, 0.0)});
+
+        for loss in [1.0, 2.0] {
+            original.begin_step();
+            original.end_epoch(Some(loss));
+        }
+
+        let mut restored = Scheduled::new(Adam::with_rate(0.0),
+            LinearWarmup{n_steps: 3, schedule: ReduceOnPlateau::new(1.0, 0.5, 0, 0.0, 0.0)});
+        let network = Network::from_geometry(&vec![1, 1]);
+        restored.import_state(&original.export_state(), &network).unwrap();
+        assert_eq!(restored.export_state(), original.export_state());
+        assert_eq!(restored.step(), 2);
+        restored.begin_step();
+        assert!((restored.rate() - 0.05).abs() < 1e-7);
+        assert!(Scheduled::new(Sgd::new(0.1), StepDecay{rate: 0.1, gamma: 0.5, step_size: 1})
+            .import_state(&Sgd::new(0.1).export_state(), &network).is_err());
+        assert!(Adam::with_rate(0.1).import_state(&original.export_state(), &network).is_err());
+    }
+}
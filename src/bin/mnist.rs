@@ -16,23 +16,53 @@ const OUTPUT_NEURONS_NUMBER: usize = 10;
 const NETWORK_GEOMETRY: [usize; 4] = [IMG_SIZE_BYTES, 16, 8, OUTPUT_NEURONS_NUMBER];
 const MNIST_OUTPUT_LAYER_SIZE: usize = 10;  // Mnist is a handwritten digits annotated database, 10 digits
 const TRAINING_RATE: f32 = 0.0001;
-const ACTIVATION_FUNCTION: algorithm::ActivationFunction = algorithm::func::activation_step;
-const ACTIVATION_FUNCTION_DERIVATIVE: algorithm::ActivationFunction
-    = algorithm::func::activation_step_d;
-const COST_FUNCTION_DERIVATIVE: algorithm::CostFunctionDerivative
-    = algorithm::func::cost_mse_d;
-const VECTOR_COST_FUNCTION: algorithm::VectorCostFunction
-    = algorithm::func::sum_squared_errors_vector_cost_function;
-
-/// Encapsulates traininig state, so it can be resumed later
-struct MnistTrainingState<'a> {
-    dataset: &'a mnist::Mnist,
-    base_offset: usize,
+/// Number of update steps the rate is halved after
+const RATE_DECAY_PERIOD: u64 = 500;
+/// L2 regularization of the weights
+const L2: f32 = 1e-4;
+const BATCH_SIZE: usize = 16;
+const N_EPOCHS: usize = 10;
+/// Number of threads each batch is split across
+const N_THREADS: usize = 4;
+/// Seed of a new session, unless one is given on the command line
+const DEFAULT_SEED: u64 = 0;
+const HIDDEN_ACTIVATION: algorithm::ActivationFunctionFamily = algorithm::ActivationFunctionFamily::Relu;
+const OUTPUT_ACTIVATION: algorithm::ActivationFunctionFamily = algorithm::ActivationFunctionFamily::Softmax;
+const WEIGHT_INIT: algorithm::init::WeightInit = algorithm::init::WeightInit::HeNormal;
+const BIAS_INIT: algorithm::init::BiasInit = algorithm::init::BiasInit::Zeros;
+const LOSS: algorithm::loss::CategoricalCrossEntropy = algorithm::loss::CategoricalCrossEntropy;
+/// Values of k top-k accuracy is reported for
+const TOP_K: [usize; 2] = [1, 3];
+
+/// Part of the MNIST dataset. Validation images are taken from the training
+/// file past the training ones, test images come from a separate file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Split {
+    Training,
+    Validation,
+    Test,
+}
+
+impl Split {
+    const ALL: [Split; 3] = [Split::Training, Split::Validation, Split::Test];
 }
 
-impl MnistTrainingState<'_> {
-    fn offset_as_relative(&self, offset: usize) -> usize {
-        return offset + self.base_offset;
+/// A split of the MNIST dataset. Progress of a training session is tracked by
+/// checkpoints, see `CHECKPOINT_FILE`.
+struct MnistDataset<'a> {
+    images: &'a [u8],
+    labels: &'a [u8],
+}
+
+impl MnistDataset<'_> {
+    fn new(mnist: &Mnist, split: Split) -> MnistDataset<'_> {
+        let (images, labels) = match split {
+            Split::Training => (&mnist.trn_img, &mnist.trn_lbl),
+            Split::Validation => (&mnist.val_img, &mnist.val_lbl),
+            Split::Test => (&mnist.tst_img, &mnist.tst_lbl),
+        };
+
+        MnistDataset{images, labels}
     }
 }
 
@@ -40,12 +70,12 @@ impl MnistTrainingState<'_> {
 ///
 /// MNIST dataset is an annotated dataset of handwritten digits.
 /// More on that here: https://en.wikipedia.org/wiki/MNIST_database
-impl ut::data::Dataset for MnistTrainingState<'_> {
+impl ut::data::Dataset for MnistDataset<'_> {
     fn copy_training_input_signal(&self, image_index: usize,
             signal: &mut ut::data::Signal) {
-        let start_position = (self.offset_as_relative(image_index)) * IMG_SIZE_BYTES;
+        let start_position = image_index * IMG_SIZE_BYTES;
         <[u8] as ut::data::CopyConvertIntoSignal>::copy_convert_into_signal(
-            &self.dataset.trn_img[start_position..start_position + IMG_SIZE_BYTES],
+            &self.images[start_position..start_position + IMG_SIZE_BYTES],
             signal
         );
     }
@@ -53,10 +83,10 @@ impl ut::data::Dataset for MnistTrainingState<'_> {
     /// Output of 10 floats each representing a digit
     fn copy_training_output_signal(&self, image_index: usize,
             signal: &mut ut::data::Signal) {
-        let position = self.dataset.trn_lbl[self.offset_as_relative(image_index)] as usize;
+        let position = self.labels[image_index] as usize;
 
         // Initialize positions not corresponding to the current digit with 0.0
-        signal.reserve_exact(MNIST_OUTPUT_LAYER_SIZE);
+        signal.clear();
         signal.resize(MNIST_OUTPUT_LAYER_SIZE, 0.0f32);
 
         // Initialize the position corresponding to the digit with 1.0
@@ -64,13 +94,19 @@ impl ut::data::Dataset for MnistTrainingState<'_> {
     }
 
     fn length(&self) -> usize {
-        self.dataset.trn_lbl.len() - self.base_offset
+        self.labels.len()
     }
 }
 
 const NETWORK_FILE: &str = "network.bin";
+/// Seed of the session which produced `NETWORK_FILE`, stored alongside it
+const SEED_FILE: &str = "network.seed";
+/// Training session state, see `--resume`
+const CHECKPOINT_FILE: &str = "checkpoint.bin";
+/// Number of batches between automatic checkpoints
+const CHECKPOINT_PERIOD: usize = 100;
 
-fn mnist_load(training_set_length: usize, test_set_length: usize) -> Mnist {
+fn mnist_load(training_set_length: usize, validation_set_length: usize, test_set_length: usize) -> Mnist {
     use std::env::current_dir;
 
     let mut path_base = current_dir().unwrap();
@@ -79,6 +115,7 @@ fn mnist_load(training_set_length: usize, test_set_length: usize) -> Mnist {
 
     MnistBuilder::new().label_format_digit()
         .training_set_length(training_set_length.try_into().unwrap())
+        .validation_set_length(validation_set_length.try_into().unwrap())
         .test_set_length(test_set_length.try_into().unwrap())
         .base_path(path_base_str)
         .finalize()
@@ -91,6 +128,7 @@ mod test_mnist_load {
     use std::{env::current_dir};
     use super::IMG_SIZE_BYTES;
     const TRAINING_SET_LEN: usize = 100;
+    const VALIDATION_SET_LEN: usize = 20;
     const TEST_SET_LEN: usize = 10;
 
     #[test]
@@ -102,69 +140,170 @@ mod test_mnist_load {
         let Mnist {
             trn_img,
             trn_lbl,
+            val_img,
+            val_lbl,
             tst_img,
             tst_lbl,
             ..
         } = MnistBuilder::new()
             .label_format_digit()
             .training_set_length(TRAINING_SET_LEN.try_into().unwrap())
+            .validation_set_length(VALIDATION_SET_LEN.try_into().unwrap())
             .test_set_length(TEST_SET_LEN.try_into().unwrap())
             .base_path(path_base_str)
             .finalize();
         assert!(trn_img.len() == IMG_SIZE_BYTES * TRAINING_SET_LEN);
         assert!(trn_lbl.len() == TRAINING_SET_LEN);
+        assert!(val_img.len() == IMG_SIZE_BYTES * VALIDATION_SET_LEN);
+        assert!(val_lbl.len() == VALIDATION_SET_LEN);
         assert!(tst_img.len() == IMG_SIZE_BYTES * TEST_SET_LEN);
         assert!(tst_lbl.len() == TEST_SET_LEN);
     }
 }
 
-/// Trains network using back propagation algorithm. It can resume previously
-/// started training session, if ibegin_training_image > 0
-fn train_network(net: &mut network::Network, mnist: &Mnist, ibegin_training_image: usize) {
-    let mut mnist_dataset = MnistTrainingState{
-        dataset: mnist,
-        base_offset: ibegin_training_image,
-    };
-    algorithm::train_network_back_propagation(
-        net,
-        ACTIVATION_FUNCTION,
-        ACTIVATION_FUNCTION_DERIVATIVE,
-        COST_FUNCTION_DERIVATIVE,
-        TRAINING_RATE,
-        &mnist_dataset,
-        |iteration_number| {
-            log::info!("Training image {} of {}", iteration_number,
-                (&mnist_dataset as &dyn ut::data::Dataset).length());
-        }
-    );
-    // TODO: save network
+/// Start of a training session
+enum Session {
+    /// A new session of the network w/ the seed
+    New(network::Network, u64),
+    /// Continuation of a checkpointed session
+    Resumed(ut::checkpoint::Checkpoint),
 }
 
-/// Runs forward propagation on a network, measures its performance.
-fn test_network(net: &mut network::Network, mnist: &Mnist) {
-    let mut mnist_dataset = MnistTrainingState{
-        dataset: mnist,
-        base_offset: 0,
+/// Trains network using back propagation algorithm. The session is
+/// checkpointed every `CHECKPOINT_PERIOD` batches, and after each epoch.
+fn train_network(mnist: &Mnist, session: Session) {
+    let mnist_dataset = MnistDataset::new(mnist, Split::Training);
+    let mut optimizer = algorithm::regularization::Regularized::new(
+        algorithm::schedule::Scheduled::new(algorithm::optimizer::Sgd::new(TRAINING_RATE),
+            algorithm::schedule::StepDecay{rate: TRAINING_RATE, gamma: 0.5, step_size: RATE_DECAY_PERIOD}),
+        algorithm::regularization::Regularization{l2: L2, ..Default::default()});
+    let (mut net, parameters, progress) = match session {
+        Session::Resumed(checkpoint) => {
+            if let Err(e) = checkpoint.check(ut::data::Dataset::length(&mnist_dataset)) {
+                log::error!("Failed to resume the session: {}", e);
+                panic!();
+            }
+
+            if let Err(e) = algorithm::optimizer::Optimizer::import_state(&mut optimizer,
+                &checkpoint.optimizer_state, &checkpoint.network)
+            {
+                log::error!("Failed to restore the optimizer: {}", e);
+                panic!();
+            }
+
+            (checkpoint.network, checkpoint.parameters, checkpoint.progress)
+        },
+        Session::New(net, seed) => (
+            net,
+            algorithm::TrainingParameters{
+                n_epochs: N_EPOCHS,
+                batch_size: BATCH_SIZE,
+                seed,
+                n_threads: N_THREADS,
+            },
+            algorithm::TrainingProgress::default(),
+        ),
     };
-    algorithm::test_network_forward_propagation(
-        net,
-        ACTIVATION_FUNCTION,
+    let mut n_batches = 0usize;
+    let reports = algorithm::train_network_epochs_resumed(
+        &mut net,
+        &LOSS,
+        &mut optimizer,
+        &parameters,
+        progress,
         &mnist_dataset,
-        |expected_signal, actual_signal| {
-            println!("Expected digit is {}, actual digit is {},
-                vector cost function value is {}",
-                ut::signal_find_max_index(expected_signal),
-                ut::signal_find_max_index(actual_signal),
-                VECTOR_COST_FUNCTION(expected_signal, actual_signal),
-            );
+        |report| {
+            log::info!("Epoch {} of {}, mean loss {}", report.epoch + 1, parameters.n_epochs,
+                report.mean_loss);
+        },
+        |progress, net, optimizer| {
+            n_batches += 1;
+
+            if n_batches.is_multiple_of(CHECKPOINT_PERIOD) || progress.cursor == 0 {
+                let checkpoint = ut::checkpoint::Checkpoint{
+                    network: net.clone(),
+                    optimizer_state: optimizer.export_state(),
+                    parameters: parameters.clone(),
+                    progress: *progress,
+                    dataset_length: ut::data::Dataset::length(&mnist_dataset),
+                };
+
+                match ut::checkpoint::checkpoint_serialize_into_file(&checkpoint, CHECKPOINT_FILE) {
+                    Ok(_) => log::debug!("Checkpoint at epoch {}, sample {}", progress.epoch,
+                        progress.cursor),
+                    Err(e) => log::error!("Failed to save a checkpoint: {}", e),
+                }
+            }
         },
     );
+
+    if let Err(e) = reports {
+        log::error!("Failed to resume the session: {}", e);
+        panic!();
+    }
+
+    if let Err(e) = ut::network_serialize_into_file(&net, NETWORK_FILE)
+        .and_then(|_| std::fs::write(SEED_FILE, parameters.seed.to_string()).map_err(Into::into))
+    {
+        log::error!("Failed to save the network: {}", e);
+    }
 }
 
-fn make_network() -> network::Network {
+/// Runs forward propagation on a network, measures its performance on each
+/// split. The test split is the only one never seen in training.
+fn test_network(net: &network::Network, mnist: &Mnist) {
+    for split in Split::ALL {
+        let mnist_dataset = MnistDataset::new(mnist, split);
+        let (mean_loss, report) = algorithm::metrics::evaluate_classification(net, &LOSS, &mnist_dataset, &TOP_K);
+        println!("{:?} split: mean loss is {}, accuracy is {} over {} samples", split, mean_loss, report.accuracy,
+            report.n_samples);
+
+        for (digit, (scores, support)) in report.classes.iter().enumerate() {
+            println!("Digit {}: precision {}, recall {}, F1 {}, {} samples",
+                digit, scores.precision, scores.recall, scores.f1, support);
+        }
+
+        for (name, scores) in [("Macro", report.macro_average), ("Micro", report.micro_average)] {
+            println!("{} average: precision {}, recall {}, F1 {}", name, scores.precision, scores.recall,
+                scores.f1);
+        }
+
+        for (k, accuracy) in &report.top_k_accuracy {
+            println!("Top-{} accuracy is {}", k, accuracy);
+        }
+
+        println!("Confusion matrix, expected digits by rows, predicted ones by columns:");
+
+        for expected in 0..report.confusion_matrix.n_classes() {
+            let row: Vec<usize> = (0..report.confusion_matrix.n_classes())
+                .map(|predicted| report.confusion_matrix.count(expected, predicted))
+                .collect();
+            println!("{:?}", row);
+        }
+    }
+}
+
+fn make_network(seed: u64) -> network::Network {
     let network = match ut::network_deserialize_from_file(NETWORK_FILE) {
-        Err(_) => network::Network::from_geometry(&NETWORK_GEOMETRY.into()),
         Ok(net) => net,
+        // No network yet, start a new one
+        Err(rusty_props::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            let mut net = network::Network::from_geometry(&NETWORK_GEOMETRY.into());
+            algorithm::init::network_init(&mut net, WEIGHT_INIT, BIAS_INIT,
+                &mut ut::rng_from_seed(seed, ut::RngStream::Initialization));
+
+            for ilayer in 1..net.n_layers() - 1 {
+                net.set_activation(ilayer, HIDDEN_ACTIVATION);
+            }
+
+            net.set_activation(net.n_layers() - 1, OUTPUT_ACTIVATION);
+            net
+        },
+        // A damaged or unsupported file must not be replaced w/ a random network
+        Err(e) => {
+            log::error!("Failed to load {}: {}", NETWORK_FILE, e);
+            panic!();
+        },
     };
 
     // Make sure that geometry is suitable for the purposes of the ongoing task
@@ -179,26 +318,56 @@ fn make_network() -> network::Network {
     network
 }
 
+/// Usage: `mnist train [SEED]`, `mnist --resume`, or `mnist test`.
+///
+/// `train` continues training `NETWORK_FILE`, or a new network, for
+/// `N_EPOCHS`. `--resume` continues the session saved in `CHECKPOINT_FILE`.
 pub fn main() {
     use std::env::args;
     use env_logger;
     const TRAINING_SET_LEN: usize = 2000;
-    const TEST_SET_LEN: usize = 100;
+    const VALIDATION_SET_LEN: usize = 500;
+    const TEST_SET_LEN: usize = 1000;
 
     env_logger::init();
     let args: Vec<String> = args().collect();
     log::trace!("Arguments: {:?}", &args);
-    let mnist = mnist_load(TRAINING_SET_LEN, TEST_SET_LEN);
-    let starting_image_index = args[1].parse::<i32>().unwrap();  // If true, there will be attempt to load an existing network
-    log::trace!("{}", starting_image_index);
-    let should_run_recognition = starting_image_index < 0;
-    let mut network = make_network();
-
-    if should_run_recognition {
-        log::info!("Starting recognition");
-        test_network(&mut network, &mnist);
-    } else {
-        log::info!("Continuing training from image {}", starting_image_index + 1);
-        train_network(&mut network, &mnist, starting_image_index as usize);
+    let mnist = mnist_load(TRAINING_SET_LEN, VALIDATION_SET_LEN, TEST_SET_LEN);
+
+    match args.get(1).map(String::as_str) {
+        Some("test") => {
+            let network = make_network(DEFAULT_SEED);
+            log::info!("Starting recognition");
+            test_network(&network, &mnist);
+        },
+        Some("train") => {
+            // Seed given explicitly, or the one the stored network was trained w/
+            let seed = args.get(2)
+                .map(|arg| arg.parse::<u64>().unwrap_or_else(|e| {
+                    log::error!("Invalid SEED {:?}: {}", arg, e);
+                    std::process::exit(2);
+                }))
+                .or_else(|| std::fs::read_to_string(SEED_FILE).ok()
+                    .and_then(|content| content.trim().parse::<u64>().ok()))
+                .unwrap_or(DEFAULT_SEED);
+            log::info!("Session seed {}", seed);
+            train_network(&mnist, Session::New(make_network(seed), seed));
+        },
+        Some("--resume") => {
+            let checkpoint = match ut::checkpoint::checkpoint_deserialize_from_file(CHECKPOINT_FILE) {
+                Ok(checkpoint) => checkpoint,
+                Err(e) => {
+                    log::error!("Failed to load {}: {}", CHECKPOINT_FILE, e);
+                    panic!();
+                },
+            };
+            log::info!("Resuming from epoch {}, sample {}", checkpoint.progress.epoch + 1,
+                checkpoint.progress.cursor);
+            train_network(&mnist, Session::Resumed(checkpoint));
+        },
+        _ => {
+            log::error!("Usage: {} train [SEED] | --resume | test", args[0]);
+            panic!();
+        },
     }
 }
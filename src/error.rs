@@ -0,0 +1,83 @@
+//! Errors reported by the fallible API of the crate.
+
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A signal, a buffer, or a layer is of unexpected length
+    ShapeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// Geometry w/o layers, or w/ an empty layer, or a network whose
+    /// buffers do not fit its geometry
+    InvalidGeometry(Vec<usize>),
+    /// A network has no layers to operate on
+    EmptyNetwork,
+    /// Stored data does not start w/ the model format's magic
+    UnknownFormat,
+    /// Stored data is of a format version this build cannot read
+    UnsupportedVersion(u32),
+    /// Stored data's checksum does not match its contents
+    ChecksumMismatch,
+    /// Stored data is well-formed, but its contents are inconsistent, e.g.
+    /// weights do not fit the geometry
+    InconsistentData(String),
+    /// Optimizer state was exported by a different kind of optimizer
+    OptimizerStateMismatch(String),
+    Io(std::io::Error),
+    Serialization(bincode::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Checks that `actual` length equals `expected`
+    pub fn check_len(expected: usize, actual: usize) -> Result<()> {
+        if expected == actual {
+            Ok(())
+        } else {
+            Err(Error::ShapeMismatch{expected, actual})
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ShapeMismatch{expected, actual} =>
+                write!(f, "expected length {}, got {}", expected, actual),
+            Error::InvalidGeometry(geometry) => write!(f, "invalid geometry {:?}", geometry),
+            Error::EmptyNetwork => write!(f, "network has no layers"),
+            Error::UnknownFormat => write!(f, "not a model file"),
+            Error::UnsupportedVersion(version) => write!(f, "unsupported format version {}", version),
+            Error::ChecksumMismatch => write!(f, "checksum mismatch"),
+            Error::InconsistentData(what) => write!(f, "inconsistent data: {}", what),
+            Error::OptimizerStateMismatch(what) => write!(f, "optimizer state mismatch: {}", what),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Serialization(e) => write!(f, "serialization error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Serialization(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(e: bincode::Error) -> Error {
+        Error::Serialization(e)
+    }
+}
@@ -1,3 +1,6 @@
 pub mod network;
 pub mod algorithm;
 pub mod ut;
+pub mod error;
+
+pub use error::Error;
@@ -1,4 +1,3 @@
-
 ///
 
 pub use std;
@@ -6,27 +5,191 @@ use std::{assert, iter::Iterator, vec::Vec, iter::Flatten, iter::FlatMap, iter::
 use crate::ut::data::Signal;
 use core::cmp;
 use crate::ut;
+use crate::algorithm::{ActivationFunctionFamily, ForwardPropagation, Workspace, BatchWorkspace, kernel};
+use crate::error::{Error, Result};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
 
 pub type Edge = Vec<Vec<f32>>;
 pub type Coeff = Vec<f32>;
-pub type LayerTuple<'a> = (&'a Coeff, &'a Coeff, &'a Edge, &'a Edge);
-pub type OwnedLayerTuple = (Coeff, Coeff, Edge, Edge);
+pub type LayerTuple<'a> = (&'a Coeff, &'a Coeff, &'a Matrix, &'a Coeff, ActivationFunctionFamily);
+pub type OwnedLayerTuple = (Coeff, Coeff, Matrix, Coeff, ActivationFunctionFamily);
+/// Layer representation used before biases were moved onto nodes: one bias
+/// per edge, `b[ifrom][ito]`
+pub type LegacyOwnedLayerTuple = (Coeff, Coeff, Matrix, Matrix);
+
+/// Dense row-major matrix stored in a single contiguous buffer.
+///
+/// Edge coefficients of a layer are laid out so that a row corresponds to an
+/// originating node, and a column corresponds to a destination node. Walking
+/// along a row is therefore walking through adjacent memory.
+#[derive(Clone, Debug)]
+pub struct Matrix {
+    data: Coeff,
+    n_rows: usize,
+    n_cols: usize,
+}
+
+impl Matrix {
+    pub fn new(n_rows: usize, n_cols: usize, val: f32) -> Matrix {
+        let mut data = Coeff::new();
+        data.reserve_exact(n_rows * n_cols);
+        data.resize(n_rows * n_cols, val);
+
+        Matrix{data, n_rows, n_cols}
+    }
+
+    /// Constructs a matrix from nested vectors. Returns `None`, if the rows
+    /// differ in length.
+    pub fn from_rows(rows: &Edge) -> Option<Matrix> {
+        let n_rows = rows.len();
+        let n_cols = rows.first().map_or(0, |row| row.len());
+
+        if !rows.iter().all(|row| row.len() == n_cols) {
+            return None;
+        }
+
+        let mut data = Coeff::new();
+        data.reserve_exact(n_rows * n_cols);
+        rows.iter().for_each(|row| data.extend_from_slice(row));
+
+        Some(Matrix{data, n_rows, n_cols})
+    }
+
+    /// Constructs a matrix from a buffer of rows laid out one after another.
+    /// Returns `None`, if the buffer's length is not `n_rows * n_cols`.
+    pub fn from_vec(n_rows: usize, n_cols: usize, data: Coeff) -> Option<Matrix> {
+        if data.len() == n_rows * n_cols {
+            Some(Matrix{data, n_rows, n_cols})
+        } else {
+            None
+        }
+    }
+
+    /// Changes the shape, reusing the buffer. Contents are unspecified
+    /// afterwards.
+    pub fn reshape(&mut self, n_rows: usize, n_cols: usize) {
+        self.data.resize(n_rows * n_cols, 0.0f32);
+        self.n_rows = n_rows;
+        self.n_cols = n_cols;
+    }
+
+    /// `out += self * rhs`
+    ///
+    /// Pre: `self.n_cols() == rhs.n_rows()`, `out` is `self.n_rows()` by
+    /// `rhs.n_cols()`
+    pub fn mul_add_into(&self, rhs: &Matrix, out: &mut Matrix) {
+        assert!(self.n_cols == rhs.n_rows && out.n_rows == self.n_rows && out.n_cols == rhs.n_cols);
+
+        // i-k-j order, so both `rhs` and `out` are walked along their rows
+        for irow in 0..self.n_rows {
+            let out_row = out.row_mut(irow);
+
+            for (k, lhs) in self.row(irow).iter().enumerate() {
+                kernel::axpy(*lhs, rhs.row(k), out_row);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn n_rows(&self) -> usize {
+        self.n_rows
+    }
+
+    #[inline]
+    pub fn n_cols(&self) -> usize {
+        self.n_cols
+    }
+
+    /// Distance between the beginnings of adjacent rows in the underlying buffer
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.n_cols
+    }
+
+    #[inline]
+    fn offset(&self, irow: usize, icol: usize) -> usize {
+        debug_assert!(irow < self.n_rows && icol < self.n_cols);
+        irow * self.stride() + icol
+    }
+
+    #[inline]
+    pub fn get(&self, irow: usize, icol: usize) -> f32 {
+        self.data[self.offset(irow, icol)]
+    }
 
+    #[inline]
+    pub fn set(&mut self, irow: usize, icol: usize, val: f32) {
+        let offset = self.offset(irow, icol);
+        self.data[offset] = val;
+    }
+
+    #[inline]
+    pub fn row(&self, irow: usize) -> &[f32] {
+        let begin = irow * self.stride();
+        &self.data[begin..begin + self.n_cols]
+    }
+
+    #[inline]
+    pub fn row_mut(&mut self, irow: usize) -> &mut [f32] {
+        let begin = irow * self.stride();
+        &mut self.data[begin..begin + self.n_cols]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item=&[f32]> {
+        (0..self.n_rows).map(move |irow| self.row(irow))
+    }
+
+    /// The underlying buffer, row after row
+    #[inline]
+    pub fn as_slice(&self) -> &[f32] {
+        &self.data
+    }
+
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [f32] {
+        &mut self.data
+    }
+
+    pub fn fill(&mut self, val: f32) {
+        self.data.fill(val);
+    }
+}
+
+/// Matrices are stored as a sequence of rows, which is binary compatible with
+/// `Edge`
+impl Serialize for Matrix {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.rows())
+    }
+}
+
+impl<'de> Deserialize<'de> for Matrix {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Matrix, D::Error> {
+        let rows = Edge::deserialize(deserializer)?;
+
+        Matrix::from_rows(&rows)
+            .ok_or_else(|| serde::de::Error::custom("matrix rows differ in length"))
+    }
+}
+
+#[derive(Clone)]
 pub struct Layer {
     /// Weighed sum from the previous layer
     z: Coeff,
     /// Activation function of the weighed sum
     a: Coeff,
-    /// Weigts
-    w: Edge,
-    /// Biases
-    b: Edge,
+    /// Weigts, `w[ifrom][ito]`
+    w: Matrix,
+    /// Biases, one per node
+    b: Coeff,
+    /// Activation function applied to the weighed sums
+    activation: ActivationFunctionFamily,
 }
 
 impl Layer {
     /// Provides native representation, e.g. for serialization libraries
     pub fn as_tuple(&self) -> LayerTuple {
-        (&self.z, &self.a, &self.w, &self.b)
+        (&self.z, &self.a, &self.w, &self.b, self.activation)
     }
 
     pub fn from_layer_tuple(layer_tuple: LayerTuple) -> Layer {
@@ -35,6 +198,30 @@ impl Layer {
             a: layer_tuple.1.clone(),
             w: layer_tuple.2.clone(),
             b: layer_tuple.3.clone(),
+            activation: layer_tuple.4,
+        }
+    }
+
+    /// Converts a layer w/ per-edge biases. Biases of the edges sharing a
+    /// destination node are summed up, which preserves the weighted sums
+    /// produced by the layer. The layer is given ReLU, the activation of
+    /// legacy hidden layers, see `Network::from_legacy_layer_tuple_vec`.
+    pub fn from_legacy_layer_tuple(layer_tuple: &LegacyOwnedLayerTuple) -> Layer {
+        let b_edge = &layer_tuple.3;
+        let b = (0..b_edge.n_cols())
+            .map(|ito| b_edge.rows().map(|row| row[ito]).sum())
+            .collect::<Coeff>();
+
+        Layer {
+            z: layer_tuple.0.clone(),
+            a: layer_tuple.1.clone(),
+            w: layer_tuple.2.clone(),
+            b,
+            activation: if layer_tuple.0.is_empty() {
+                ActivationFunctionFamily::Identity
+            } else {
+                ActivationFunctionFamily::Relu
+            },
         }
     }
 }
@@ -45,6 +232,7 @@ impl Layer {
 /// Layers are counted from left to right (from input to output), starting from
 /// 0. Edges have the same level as their destination nodes.
 ///
+#[derive(Clone)]
 pub struct Network {
     layers: std::vec::Vec<Layer>
 }
@@ -69,13 +257,10 @@ impl Network {
     /// Constructs a network from a set of weights. A part of deserialization
     /// process.
     pub fn from_layer_tuple_vec(layer_tuple_vec: &Vec<OwnedLayerTuple>) -> Network {
-        let geometry = layer_tuple_vec.iter()
-            .map(|layer_tuple| layer_tuple.0.len())
-            .collect::<Vec<usize>>();
         let layers = layer_tuple_vec
             .iter()
             .map(|layer_tuple| Layer::from_layer_tuple(
-                (&layer_tuple.0, &layer_tuple.1, &layer_tuple.2, &layer_tuple.3)
+                (&layer_tuple.0, &layer_tuple.1, &layer_tuple.2, &layer_tuple.3, layer_tuple.4)
             ))
             .collect::<Vec<Layer>>();
         let network = Network{layers};
@@ -83,6 +268,40 @@ impl Network {
         network
     }
 
+    /// Constructs a network from a set of weights and per-edge biases. Used
+    /// to migrate networks stored in the legacy format.
+    ///
+    /// Legacy networks output the weighed sums of the last layer, so it is
+    /// given the identity activation.
+    pub fn from_legacy_layer_tuple_vec(layer_tuple_vec: &[LegacyOwnedLayerTuple]) -> Network {
+        let mut layers: Vec<Layer> = layer_tuple_vec.iter().map(Layer::from_legacy_layer_tuple).collect();
+
+        if let Some(output) = layers.last_mut() {
+            output.activation = ActivationFunctionFamily::Identity;
+        }
+
+        Network{layers}
+    }
+
+    /// Checks that the sizes of weights, biases, and intermediate buffers
+    /// agree with the number of nodes on each layer. Deserialized networks
+    /// are expected to pass this check.
+    pub fn is_consistent(&self) -> bool {
+        let mut size_prev = 0;
+
+        self.layers.iter().all(|layer| {
+            let nnodes = layer.a.len();
+            let nnodes_inner = if size_prev == 0 { 0 } else { nnodes };
+            let ret = layer.z.len() == nnodes_inner
+                && layer.b.len() == nnodes_inner
+                && layer.w.n_rows() == size_prev
+                && (size_prev == 0 || layer.w.n_cols() == nnodes);
+            size_prev = nnodes;
+
+            ret
+        })
+    }
+
     /// Number of layers in the network
     #[inline]
     pub fn n_layers(&self) -> usize {
@@ -101,7 +320,8 @@ impl Network {
     /// `geometry` specifies how many nodes reside on a layer. Indices of
     /// `geometry` items are layer indices in the network.
     ///
-    /// Post: the network will be initialized w/ NAN values
+    /// Post: the network will be initialized w/ NAN values, and ReLU
+    /// activation on each layer but the input one
     pub fn from_geometry(geometry: &std::vec::Vec<usize>) -> Network {
         let mut network = Network{
             layers: std::vec::Vec::new(),
@@ -113,27 +333,20 @@ impl Network {
             let mut layer = Layer{
                 a: Vec::new(),
                 z: Vec::new(),
-                w: Vec::new(),
+                w: Matrix::new(size_prev, *nnodes, f32::NAN),
                 b: Vec::new(),
+                activation: ActivationFunctionFamily::Identity,
             };
             // TODO: optimize input and output layers. Note the necessity to ensure size consistency when performing (de)serialization
             layer.a.reserve_exact(*nnodes);
             layer.a.resize(*nnodes, f32::NAN);
-            layer.w.reserve_exact(size_prev);
-            layer.w.resize(size_prev, Vec::new());
-            layer.b.reserve_exact(size_prev);
-            layer.b.resize(size_prev, Vec::new());
 
             if size_prev != 0 {
                 layer.z.reserve_exact(*nnodes);
                 layer.z.resize(*nnodes, f32::NAN);
-
-                for i in 0..size_prev {
-                    layer.w[i].reserve_exact(*nnodes);
-                    layer.w[i].resize(*nnodes, f32::NAN);
-                    layer.b[i].reserve_exact(*nnodes);
-                    layer.b[i].resize(*nnodes, f32::NAN);
-                }
+                layer.b.reserve_exact(*nnodes);
+                layer.b.resize(*nnodes, f32::NAN);
+                layer.activation = ActivationFunctionFamily::Relu;
             }
 
             network.layers.push(layer);
@@ -143,31 +356,127 @@ impl Network {
         return network;
     }
 
+    /// Same as `from_geometry`, but rejects geometries w/o layers, or w/ empty
+    /// layers
+    pub fn try_from_geometry(geometry: &[usize]) -> Result<Network> {
+        if geometry.is_empty() || geometry.contains(&0) {
+            return Err(Error::InvalidGeometry(geometry.to_vec()));
+        }
+
+        Ok(Network::from_geometry(&geometry.to_vec()))
+    }
+
+    /// Copies the signal into the input layer.
+    ///
+    /// Pre: the signal's length is that of the input layer
     #[inline]
     pub fn init_input_layer(&mut self, signal: &Signal) {
-        assert!(self.layers.len() > 0);
-        self.layers[0].a = signal.to_vec();
+        if let Err(e) = self.try_init_input_layer(signal) {
+            panic!("Failed to initialize the input layer: {}", e);
+        }
     }
 
+    pub fn try_init_input_layer(&mut self, signal: &[f32]) -> Result<()> {
+        let input = &mut self.layers.first_mut().ok_or(Error::EmptyNetwork)?.a;
+        Error::check_len(input.len(), signal.len())?;
+        input.copy_from_slice(signal);
+
+        Ok(())
+    }
+
+    /// Activations of the output layer
     #[inline]
     pub fn output_layer(&self) -> &Signal {
-        &self.layers[self.n_layers() - 1].z
+        &self.layers[self.n_layers() - 1].a
     }
 
-    #[inline]
-    /// Returns (WEIGHT, BIAS) pair
-    pub fn edge_coef_wb(&self, ilayer: usize, ifrom: usize, ito: usize) -> (f32, f32) {
-        (self.w(ilayer, ifrom, ito), self.b(ilayer, ifrom, ito))
+    pub fn try_output_layer(&self) -> Result<&Signal> {
+        self.layers.last().map(|layer| &layer.a).ok_or(Error::EmptyNetwork)
+    }
+
+    /// Runs inference w/o modifying the network, see
+    /// `ForwardPropagation::try_predict`
+    pub fn predict(&self, input: &[f32], workspace: &mut Workspace) -> Signal {
+        match self.try_predict(input, workspace) {
+            Ok(output) => output,
+            Err(e) => panic!("Failed to run inference: {}", e),
+        }
+    }
+
+    pub fn try_predict(&self, input: &[f32], workspace: &mut Workspace) -> Result<Signal> {
+        ForwardPropagation.try_predict(self, input, workspace).cloned()
+    }
+
+    /// Runs inference on a batch of inputs, one per row, see
+    /// `ForwardPropagation::try_predict_batch`
+    pub fn predict_batch(&self, inputs: &Matrix, workspace: &mut BatchWorkspace) -> Matrix {
+        match self.try_predict_batch(inputs, workspace) {
+            Ok(outputs) => outputs,
+            Err(e) => panic!("Failed to run inference: {}", e),
+        }
+    }
+
+    pub fn try_predict_batch(&self, inputs: &Matrix, workspace: &mut BatchWorkspace) -> Result<Matrix> {
+        ForwardPropagation.try_predict_batch(self, inputs, workspace).cloned()
     }
 
     #[inline]
     pub fn w(&self, ilayer: usize, ifrom: usize, ito: usize) -> f32 {
-        self.layers[ilayer].w[ifrom][ito]
+        self.layers[ilayer].w.get(ifrom, ito)
+    }
+
+    /// Bias of node `inode` on layer `ilayer`
+    #[inline]
+    pub fn b(&self, ilayer: usize, inode: usize) -> f32 {
+        self.layers[ilayer].b[inode]
+    }
+
+    /// Weights of edges ending on layer `ilayer`, `ifrom` x `ito`
+    #[inline]
+    pub fn w_matrix(&self, ilayer: usize) -> &Matrix {
+        &self.layers[ilayer].w
+    }
+
+    /// Biases of the nodes of layer `ilayer`
+    #[inline]
+    pub fn b_vec(&self, ilayer: usize) -> &Coeff {
+        &self.layers[ilayer].b
+    }
+
+    #[inline]
+    pub fn w_matrix_mut(&mut self, ilayer: usize) -> &mut Matrix {
+        &mut self.layers[ilayer].w
     }
 
     #[inline]
-    pub fn b(&self, ilayer: usize, ifrom: usize, ito: usize) -> f32 {
-        self.layers[ilayer].b[ifrom][ito]
+    pub fn b_vec_mut(&mut self, ilayer: usize) -> &mut Coeff {
+        &mut self.layers[ilayer].b
+    }
+
+    /// Returns (Z, mutable A) of layer `ilayer`
+    #[inline]
+    pub fn layer_activation_mut(&mut self, ilayer: usize) -> (&Coeff, &mut Coeff) {
+        let layer = &mut self.layers[ilayer];
+
+        (&layer.z, &mut layer.a)
+    }
+
+    /// Activations of a layer
+    #[inline]
+    pub fn a_vec(&self, ilayer: usize) -> &Coeff {
+        &self.layers[ilayer].a
+    }
+
+    /// Splits the network so that layer `ilayer` can be updated from the
+    /// activations of its predecessor. Returns (A of `ilayer - 1`, W, B,
+    /// mutable Z of `ilayer`).
+    #[inline]
+    pub fn layer_propagation_mut(&mut self, ilayer: usize) -> (&Coeff, &Matrix, &Coeff, &mut Coeff) {
+        assert!(ilayer > 0);
+        let (head, tail) = self.layers.split_at_mut(ilayer);
+        let layer = &mut tail[0];
+
+        (&head[ilayer - 1].a, &layer.w, &layer.b, &mut layer.z)
     }
 
     /// Access activation value on a specified node and layer
@@ -182,14 +491,31 @@ impl Network {
         self.layers[ilayer].z[inode]
     }
 
+    /// Weighed sums of a layer
+    #[inline]
+    pub fn z_vec(&self, ilayer: usize) -> &Coeff {
+        &self.layers[ilayer].z
+    }
+
     #[inline]
     pub fn set_w(&mut self, ilayer: usize, ifrom: usize, ito: usize, val: f32) {
-        self.layers[ilayer].w[ifrom][ito] = val
+        self.layers[ilayer].w.set(ifrom, ito, val)
+    }
+
+    #[inline]
+    pub fn set_b(&mut self, ilayer: usize, inode: usize, val: f32) {
+        self.layers[ilayer].b[inode] = val
+    }
+
+    /// Activation function of layer `ilayer`
+    #[inline]
+    pub fn activation(&self, ilayer: usize) -> ActivationFunctionFamily {
+        self.layers[ilayer].activation
     }
 
     #[inline]
-    pub fn set_b(&mut self, ilayer: usize, ifrom: usize, ito: usize, val: f32) {
-        self.layers[ilayer].b[ifrom][ito] = val
+    pub fn set_activation(&mut self, ilayer: usize, activation: ActivationFunctionFamily) {
+        self.layers[ilayer].activation = activation;
     }
 
     #[inline]
@@ -216,16 +542,30 @@ impl Network {
 
     pub fn reset(&mut self) {
         for layer in &mut self.layers {
-            layer.a.resize(layer.a.len(), f32::NAN);
-            layer.z.resize(layer.z.len(), f32::NAN);
+            layer.a.fill(f32::NAN);
+            layer.z.fill(f32::NAN);
+            layer.w.fill(f32::NAN);
+            layer.b.fill(f32::NAN);
+        }
+    }
 
-            for w in layer.w.iter_mut().flat_map(|edge| edge.iter_mut()) {
-                *w = f32::NAN;
-            }
+    /// Sets all weights and biases to `val`, leaving activations intact
+    pub fn fill_parameters(&mut self, val: f32) {
+        for layer in &mut self.layers {
+            layer.w.fill(val);
+            layer.b.fill(val);
+        }
+    }
 
-            for b in layer.b.iter_mut().flat_map(|edge| edge.iter_mut()) {
-                *b = f32::NAN;
-            }
+    /// Copies weights, biases, and activation functions from a network of the
+    /// same geometry
+    pub fn copy_parameters_from(&mut self, other: &Network) {
+        assert!(self.is_match_geometry(&other.geometry()));
+
+        for (layer, other) in self.layers.iter_mut().zip(&other.layers) {
+            layer.w.as_mut_slice().copy_from_slice(other.w.as_slice());
+            layer.b.copy_from_slice(&other.b);
+            layer.activation = other.activation;
         }
     }
 
@@ -240,7 +580,29 @@ impl Network {
 
 #[cfg(test)]
 mod test_network {
-    use super::Network;
+    use super::{Network, Matrix, Error};
+
+    #[test]
+    fn fallible_construction() {
+        assert!(matches!(Network::try_from_geometry(&[]), Err(Error::InvalidGeometry(_))));
+        assert!(matches!(Network::try_from_geometry(&[2, 0, 1]), Err(Error::InvalidGeometry(_))));
+        let mut network = Network::try_from_geometry(&[2, 3, 1]).unwrap();
+        assert!(matches!(network.try_init_input_layer(&[1.0]),
+            Err(Error::ShapeMismatch{expected: 2, actual: 1})));
+        assert!(network.try_init_input_layer(&[1.0, 2.0]).is_ok());
+        assert_eq!(network.a_vec(0), &vec![1.0f32, 2.0]);
+        assert_eq!(network.try_output_layer().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn matrix_product() {
+        let lhs = Matrix::from_vec(2, 3, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+        let rhs = Matrix::from_vec(3, 2, vec![1.0, 0.0, 0.0, 1.0, 1.0, 1.0]).unwrap();
+        let mut out = Matrix::new(2, 2, 1.0);
+        lhs.mul_add_into(&rhs, &mut out);
+        assert_eq!(out.as_slice(), &[5.0, 6.0, 11.0, 12.0]);
+        assert!(Matrix::from_vec(2, 2, vec![0.0; 3]).is_none());
+    }
 
     #[test]
     fn construction() {
@@ -251,6 +613,33 @@ mod test_network {
             assert_eq!(network.layer_len(i), geometry[i]);
         }
     }
+
+    #[test]
+    fn matrix_layout() {
+        let geometry = vec![3, 2];
+        let mut network = Network::from_geometry(&geometry);
+
+        for (ifrom, ito) in network.edge_index_iter(1) {
+            network.set_w(1, ifrom, ito, (ifrom * 10 + ito) as f32);
+        }
+
+        let w = network.w_matrix(1);
+        assert_eq!((w.n_rows(), w.n_cols(), w.stride()), (3, 2, 2));
+        assert_eq!(w.as_slice(), &[0.0, 1.0, 10.0, 11.0, 20.0, 21.0]);
+        assert_eq!(w.row(2), &[20.0, 21.0]);
+    }
+
+    #[test]
+    fn legacy_bias_collapse() {
+        let legacy = vec![
+            (vec![], vec![0.0; 2], Matrix::new(0, 0, 0.0), Matrix::new(0, 0, 0.0)),
+            (vec![0.0; 3], vec![0.0; 3], Matrix::new(2, 3, 0.5), Matrix::new(2, 3, 0.25)),
+        ];
+        let network = Network::from_legacy_layer_tuple_vec(&legacy);
+
+        assert!(network.is_consistent());
+        assert_eq!(network.b_vec(1), &vec![0.5; 3]);
+    }
 }
 
 impl cmp::PartialEq for Network {
@@ -263,10 +652,10 @@ impl cmp::PartialEq for Network {
             for i in 0..geometry.len() {
                 res = res && ut::vecf32_float_safe_is_eq(&self.layers[i].a, &other.layers[i].a);
                 res = res && ut::vecf32_float_safe_is_eq(&self.layers[i].z, &other.layers[i].z);
-                res = res && self.layers[i].b.iter().zip(other.layers[i].b.iter())
-                    .fold(true, |acc, item| acc && ut::vecf32_float_safe_is_eq(&item.0, &item.1));
-                res = res && self.layers[i].w.iter().zip(other.layers[i].w.iter())
-                    .fold(true, |acc, item| acc && ut::vecf32_float_safe_is_eq(&item.0, &item.1));
+                res = res && ut::vecf32_float_safe_is_eq(&self.layers[i].b, &other.layers[i].b);
+                res = res && self.layers[i].activation == other.layers[i].activation;
+                res = res && ut::vecf32_float_safe_is_eq(self.layers[i].w.as_slice(),
+                    other.layers[i].w.as_slice());
 
                 if !res {
                     break
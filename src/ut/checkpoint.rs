@@ -0,0 +1,154 @@
+//! Snapshots of training sessions, so they can be resumed.
+
+use crate::algorithm::{TrainingParameters, TrainingProgress, optimizer::OptimizerState};
+use crate::network::Network;
+use crate::error::{Error, Result};
+use super::format;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, BufReader},
+    path::Path,
+};
+use bincode;
+
+/// State of a training session. The state of the shuffling generator is
+/// implied by the seed and the progress.
+#[derive(Clone)]
+pub struct Checkpoint {
+    pub network: Network,
+    pub optimizer_state: OptimizerState,
+    pub parameters: TrainingParameters,
+    pub progress: TrainingProgress,
+    /// Number of samples of the dataset the session trains on
+    pub dataset_length: usize,
+}
+
+impl Checkpoint {
+    /// Checks that the session can be resumed over a dataset of
+    /// `dataset_length` samples
+    pub fn check(&self, dataset_length: usize) -> Result<()> {
+        if dataset_length != self.dataset_length {
+            return Err(Error::InconsistentData(format!("session over {} samples, the dataset has {}",
+                self.dataset_length, dataset_length)));
+        }
+
+        self.progress.check(&self.parameters, dataset_length)
+    }
+}
+
+/// On-disk layout of `Checkpoint`. The network is stored in the model format.
+type CheckpointTuple = (
+    Vec<u8>,
+    OptimizerState,
+    (u64 /* n_epochs */, u64 /* batch_size */, u64 /* seed */, u64 /* n_threads */),
+    (u64 /* epoch */, u64 /* cursor */, f32 /* loss_sum */),
+    u64, /* dataset_length */
+);
+
+/// Packs a checkpoint into a binary file. The file is replaced at once, so an
+/// interrupted save leaves the previous checkpoint intact.
+pub fn checkpoint_serialize_into_file(checkpoint: &Checkpoint, fname: &str) -> Result<()> {
+    let path_tmp = format!("{}.tmp", fname);
+    let file_out = File::create(&path_tmp)?;
+    let stream_out = BufWriter::new(file_out);
+    let Checkpoint{network, optimizer_state, parameters, progress, dataset_length} = checkpoint;
+    let network_bytes = format::network_encode(network)?;
+    let checkpoint_tuple: CheckpointTuple = (
+        network_bytes,
+        optimizer_state.clone(),
+        (parameters.n_epochs as u64, parameters.batch_size as u64, parameters.seed,
+            parameters.n_threads as u64),
+        (progress.epoch as u64, progress.cursor as u64, progress.loss_sum),
+        *dataset_length as u64,
+    );
+
+    bincode::serialize_into(stream_out, &checkpoint_tuple)?;
+    fs::rename(path_tmp, fname)?;
+
+    Ok(())
+}
+
+/// Unpacks a binary file into `Checkpoint` object. The progress is checked
+/// against the parameters and the stored dataset length.
+pub fn checkpoint_deserialize_from_file(fname: &str) -> Result<Checkpoint> {
+    let file_in = File::open(Path::new(fname))?;
+    let stream_in = BufReader::new(file_in);
+    let (network_bytes, optimizer_state, (n_epochs, batch_size, seed, n_threads), (epoch, cursor, loss_sum),
+        dataset_length) = bincode::deserialize_from::<_, CheckpointTuple>(stream_in)?;
+    let network = format::network_decode(&network_bytes)?;
+
+    let to_usize = |value: u64| usize::try_from(value)
+        .map_err(|_| Error::InconsistentData(format!("{} does not fit the platform", value)));
+
+    let checkpoint = Checkpoint{
+        network,
+        optimizer_state,
+        parameters: TrainingParameters{
+            n_epochs: to_usize(n_epochs)?,
+            batch_size: to_usize(batch_size)?,
+            seed,
+            n_threads: to_usize(n_threads)?,
+        },
+        progress: TrainingProgress{
+            epoch: to_usize(epoch)?,
+            cursor: to_usize(cursor)?,
+            loss_sum,
+        },
+        dataset_length: to_usize(dataset_length)?,
+    };
+    checkpoint.check(checkpoint.dataset_length)?;
+
+    Ok(checkpoint)
+}
+
+#[cfg(test)]
+mod test_checkpoint {
+    use super::*;
+    use crate::algorithm::{self, optimizer::{Optimizer, Adam}};
+    use crate::ut;
+
+    #[test]
+    fn serialize() {
+        let mut network = Network::from_geometry(&vec![3, 2, 2]);
+        algorithm::network_init_random(&mut network,
+            &mut ut::rng_from_seed(0, ut::RngStream::Initialization));
+        let mut optimizer = Adam::with_rate(0.1);
+        optimizer.begin_step();
+        optimizer.update(algorithm::optimizer::Parameter::Weights(1),
+            network.w_matrix_mut(1).as_mut_slice(), &[1.0f32; 6]);
+        let checkpoint = Checkpoint{
+            network,
+            optimizer_state: optimizer.export_state(),
+            parameters: TrainingParameters{n_epochs: 4, batch_size: 8, seed: 42, n_threads: 2},
+            progress: TrainingProgress{epoch: 2, cursor: 16, loss_sum: 1.5},
+            dataset_length: 20,
+        };
+        checkpoint_serialize_into_file(&checkpoint, "checkpoint_test.bin").unwrap();
+        let restored = checkpoint_deserialize_from_file("checkpoint_test.bin").unwrap();
+        std::fs::remove_file("checkpoint_test.bin").unwrap();
+
+        assert!(restored.network == checkpoint.network);
+        assert_eq!(restored.optimizer_state, checkpoint.optimizer_state);
+        assert_eq!(restored.parameters.seed, 42);
+        assert_eq!(restored.progress, checkpoint.progress);
+        assert!(restored.check(20).is_ok());
+        assert!(matches!(restored.check(8), Err(Error::InconsistentData(_))));
+    }
+
+    /// A checkpoint whose progress does not fit the dataset must be rejected
+    #[test]
+    fn inconsistent() {
+        let checkpoint = Checkpoint{
+            network: Network::from_geometry(&vec![2, 1]),
+            optimizer_state: Adam::with_rate(0.1).export_state(),
+            parameters: TrainingParameters{n_epochs: 4, batch_size: 4, seed: 42, n_threads: 1},
+            progress: TrainingProgress{epoch: 1, cursor: 12, loss_sum: 0.0},
+            dataset_length: 8,
+        };
+        checkpoint_serialize_into_file(&checkpoint, "checkpoint_test_inconsistent.bin").unwrap();
+        let restored = checkpoint_deserialize_from_file("checkpoint_test_inconsistent.bin");
+        std::fs::remove_file("checkpoint_test_inconsistent.bin").unwrap();
+
+        assert!(matches!(restored, Err(Error::InconsistentData(_))));
+    }
+}
@@ -47,3 +47,49 @@ impl<T> Length for [T] {
         self.len()
     }
 }
+
+/// Samples of a dataset picked by their indices
+pub struct Subset<'a, D: Dataset + ?Sized> {
+    dataset: &'a D,
+    indices: Vec<usize>,
+}
+
+impl<'a, D: Dataset + ?Sized> Subset<'a, D> {
+    /// Pre: `indices` are within the dataset
+    pub fn new(dataset: &'a D, indices: Vec<usize>) -> Subset<'a, D> {
+        Subset{dataset, indices}
+    }
+
+    /// Splits a dataset into two disjoint subsets, the second one being of
+    /// `n_holdout` samples drawn at random. The rest keep their order.
+    pub fn split_holdout<R: rand::Rng + ?Sized>(dataset: &'a D, n_holdout: usize, rng: &mut R)
+        -> (Subset<'a, D>, Subset<'a, D>)
+    {
+        let n_holdout = n_holdout.min(dataset.length());
+        let mut is_holdout = vec![false; dataset.length()];
+        rand::seq::index::sample(rng, dataset.length(), n_holdout)
+            .iter()
+            .for_each(|i| is_holdout[i] = true);
+        let (holdout, rest): (Vec<usize>, Vec<usize>) = (0..dataset.length()).partition(|i| is_holdout[*i]);
+
+        (Subset::new(dataset, rest), Subset::new(dataset, holdout))
+    }
+
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+}
+
+impl<D: Dataset + ?Sized> Dataset for Subset<'_, D> {
+    fn copy_training_input_signal(&self, image_index: usize, signal: &mut Signal) {
+        self.dataset.copy_training_input_signal(self.indices[image_index], signal);
+    }
+
+    fn copy_training_output_signal(&self, image_index: usize, signal: &mut Signal) {
+        self.dataset.copy_training_output_signal(self.indices[image_index], signal);
+    }
+
+    fn length(&self) -> usize {
+        self.indices.len()
+    }
+}
@@ -0,0 +1,158 @@
+//! Model file format.
+//!
+//! A file consists of a fixed header followed by a payload. Integers are
+//! little-endian.
+//!
+//! | Offset | Size | Field                                    |
+//! |--------|------|------------------------------------------|
+//! | 0      | 4    | Magic, `b"RPNN"`                         |
+//! | 4      | 4    | Format version, `u32`                    |
+//! | 8      | 4    | CRC-32 (IEEE) of the payload, `u32`      |
+//! | 12     | 8    | Payload length in bytes, `u64`           |
+//! | 20     | ...  | Payload                                  |
+//!
+//! Version 1 payload is bincode of `(geometry, layers)`:
+//! - `geometry: Vec<u64>`, lengths of the layers, input first;
+//! - `layers: Vec<(u8, Matrix, Vec<f32>)>`, one entry per layer but the input
+//!   one: activation function family id, weights (one row per node of the
+//!   previous layer), and biases.
+//!
+//! Only learnable parameters are stored. Weighed sums and activations are
+//! recomputed by forward propagation.
+
+use crate::algorithm::ActivationFunctionFamily;
+use crate::network::{Network, Matrix, Coeff};
+use crate::error::{Error, Result};
+use bincode;
+
+pub const MAGIC: [u8; 4] = *b"RPNN";
+pub const VERSION: u32 = 1;
+const HEADER_LEN: usize = 20;
+
+type Payload<M, C> = (Vec<u64>, Vec<(ActivationFunctionFamily, M, C)>);
+
+/// CRC-32 w/ the IEEE 802.3 polynomial
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+
+    for byte in bytes {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb88320 & mask);
+        }
+    }
+
+    !crc
+}
+
+/// Whether the bytes start w/ the magic, i.e. are not in a legacy format
+pub fn is_model(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Packs a network into the model format
+pub fn network_encode(network: &Network) -> Result<Vec<u8>> {
+    let geometry = network.geometry().iter().map(|len| *len as u64).collect();
+    let layers = (1..network.n_layers())
+        .map(|ilayer| (network.activation(ilayer), network.w_matrix(ilayer), network.b_vec(ilayer)))
+        .collect();
+    let payload = bincode::serialize::<Payload<&Matrix, &Coeff>>(&(geometry, layers))?;
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+
+    Ok(bytes)
+}
+
+/// Unpacks a network from the model format, validating the header, the
+/// checksum, and the geometry
+pub fn network_decode(bytes: &[u8]) -> Result<Network> {
+    if bytes.len() < HEADER_LEN || !is_model(bytes) {
+        return Err(Error::UnknownFormat);
+    }
+
+    let field = |offset: usize| -> [u8; 4] { bytes[offset..offset + 4].try_into().unwrap() };
+    let version = u32::from_le_bytes(field(4));
+    let checksum = u32::from_le_bytes(field(8));
+    let payload_len = u64::from_le_bytes(bytes[12..20].try_into().unwrap());
+
+    if version != VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+
+    let payload = &bytes[HEADER_LEN..];
+
+    if payload.len() as u64 != payload_len {
+        return Err(Error::InconsistentData(format!("expected {} payload bytes, got {}",
+            payload_len, payload.len())));
+    }
+
+    if crc32(payload) != checksum {
+        return Err(Error::ChecksumMismatch);
+    }
+
+    let (geometry, layers) = bincode::deserialize::<Payload<Matrix, Coeff>>(payload)?;
+    let geometry = geometry.into_iter()
+        .map(usize::try_from)
+        .collect::<std::result::Result<Vec<usize>, _>>()
+        .map_err(|_| Error::InconsistentData("geometry does not fit the platform".into()))?;
+
+    if layers.len() + 1 != geometry.len() {
+        return Err(Error::InconsistentData("geometry does not match the layers".into()));
+    }
+
+    let mut network = Network::try_from_geometry(&geometry)?;
+
+    for (ilayer, (activation, w, b)) in layers.into_iter().enumerate().map(|(i, l)| (i + 1, l)) {
+        if w.n_rows() != geometry[ilayer - 1] || w.n_cols() != geometry[ilayer]
+            || b.len() != geometry[ilayer]
+        {
+            return Err(Error::InconsistentData(format!("layer {} does not match the geometry", ilayer)));
+        }
+
+        network.set_activation(ilayer, activation);
+        *network.w_matrix_mut(ilayer) = w;
+        *network.b_vec_mut(ilayer) = b;
+    }
+
+    Ok(network)
+}
+
+#[cfg(test)]
+mod test_format {
+    use super::*;
+    use crate::{algorithm, ut};
+
+    #[test]
+    fn round_trip() {
+        let mut network = Network::from_geometry(&vec![3, 4, 2]);
+        algorithm::network_init_random(&mut network,
+            &mut ut::rng_from_seed(0, ut::RngStream::Initialization));
+        network.set_activation(2, ActivationFunctionFamily::Softmax);
+        let mut bytes = network_encode(&network).unwrap();
+        let decoded = network_decode(&bytes).unwrap();
+
+        for ilayer in 1..network.n_layers() {
+            assert_eq!(decoded.activation(ilayer), network.activation(ilayer));
+            assert_eq!(decoded.w_matrix(ilayer).as_slice(), network.w_matrix(ilayer).as_slice());
+            assert_eq!(decoded.b_vec(ilayer), network.b_vec(ilayer));
+        }
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(matches!(network_decode(&bytes), Err(Error::ChecksumMismatch)));
+        bytes[4] = 2;
+        assert!(matches!(network_decode(&bytes), Err(Error::UnsupportedVersion(2))));
+        assert!(matches!(network_decode(b"legacy"), Err(Error::UnknownFormat)));
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+}
@@ -1,11 +1,14 @@
 pub mod data;
+pub mod checkpoint;
+pub mod format;
 
 use crate::algorithm::Signal;
-use crate::network::{Network, Coeff, Edge};
+use crate::network::{Network, OwnedLayerTuple, LegacyOwnedLayerTuple};
+use crate::error::{Error, Result};
 use std::{
     vec::Vec,
-    fs::File,
-    io::{Write, BufWriter, BufReader},
+    fs::{self, File},
+    io::Write,
     path::Path,
 };
 use rand::distributions::{Distribution, Uniform};
@@ -47,37 +50,122 @@ pub fn signal_find_max_index(signal: &Signal) -> usize {
         }).0
 }
 
-pub fn vec_init_random<T: rand::distributions::uniform::SampleUniform>(vec: &mut Vec<T>, from: T, to: T) {
-    let mut rng = rand::thread_rng();
+pub fn vec_init_random<T, R>(vec: &mut Vec<T>, from: T, to: T, rng: &mut R)
+where
+    T: rand::distributions::uniform::SampleUniform,
+    R: rand::Rng + ?Sized,
+{
     let gen = Uniform::from(from..to);
 
     for s in vec {
-        *s = gen.sample(&mut rng);
+        *s = gen.sample(rng);
     }
 }
 
-/// Packs a network into a binary file
-pub fn network_serialize_into_file(network: &Network, fname: &str) -> Result<(), std::io::Error> {
-    let path_out = Path::new(fname);
-    let mut file_out = File::create(&path_out)?;
-    let stream_out = BufWriter::new(&mut file_out);
-    let layer_tuple_vec = network.as_layer_tuple_vec();
+/// Random number generator used throughout the crate. Sessions seeded
+/// identically are reproducible bit-for-bit. The algorithm is fixed, unlike
+/// that of `rand::rngs::StdRng`, which may change between versions of `rand`.
+pub type Rng = rand_chacha::ChaCha8Rng;
 
-    match bincode::serialize_into(stream_out, &layer_tuple_vec) {
-        Ok(_) => Ok(()),
-        Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e))
+/// Consumers of randomness. Each one draws from its own stream, so e.g.
+/// changing the initialization scheme does not alter the sample orders.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RngStream {
+    Initialization = 0,
+    Shuffling = 1,
+    /// Picking samples held out for validation
+    Holdout = 2,
+}
+
+/// SplitMix64 finalizer, decorrelates nearby seeds
+fn mix_seed(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+
+    x ^ (x >> 31)
+}
+
+/// Makes a generator for one of the streams of a session seeded w/ `seed`.
+/// The key is expanded from the seed by SplitMix64, and each consumer gets
+/// its own ChaCha stream.
+pub fn rng_from_seed(seed: u64, stream: RngStream) -> Rng {
+    let mut key = [0u8; 32];
+
+    for (i, chunk) in key.chunks_exact_mut(8).enumerate() {
+        let word = mix_seed(seed.wrapping_add((i as u64).wrapping_mul(0x9e3779b97f4a7c15)));
+        chunk.copy_from_slice(&word.to_le_bytes());
     }
+
+    let mut rng = <Rng as rand::SeedableRng>::from_seed(key);
+    rng.set_stream(stream as u64);
+
+    rng
+}
+
+/// Packs a network into a binary file of the model format, see `format`. The
+/// file is replaced at once, so an interrupted save leaves the previous
+/// network intact.
+pub fn network_serialize_into_file(network: &Network, fname: &str) -> Result<()> {
+    let bytes = format::network_encode(network)?;
+    let path_tmp = format!("{}.tmp", fname);
+    let mut file_out = File::create(Path::new(&path_tmp))?;
+    file_out.write_all(&bytes)?;
+    fs::rename(path_tmp, fname)?;
+
+    Ok(())
 }
 
-// /// Unpacks binary file into `Network` object
-pub fn network_deserialize_from_file(fname: &str) -> Result<Network, Box<dyn std::error::Error>> {
-    type OwnedLayerTuple = Vec<(Coeff, Coeff, Edge, Edge)>;
-    let path_in = Path::new(fname);
-    let mut file_in = File::open(&path_in)?;
-    let stream_in = BufReader::new(&mut file_in);
-    let deserialized = bincode::deserialize_from::<_, OwnedLayerTuple>(stream_in)?;
+/// Unpacks a binary file into `Network` object. Files w/o the model format
+/// header are read as legacy ones.
+pub fn network_deserialize_from_file(fname: &str) -> Result<Network> {
+    let bytes = fs::read(Path::new(fname))?;
 
-    Ok(Network::from_layer_tuple_vec(&deserialized))
+    if format::is_model(&bytes) {
+        format::network_decode(&bytes)
+    } else {
+        network_deserialize_from_legacy_bytes(&bytes)
+    }
+}
+
+/// Unpacks a network stored in one of the formats preceding the model one:
+/// a bare dump of layer tuples, or a dump of layer tuples w/ per-edge biases,
+/// which are migrated to per-node ones.
+pub fn network_deserialize_from_legacy_file(fname: &str) -> Result<Network> {
+    network_deserialize_from_legacy_bytes(&fs::read(Path::new(fname))?)
+}
+
+fn network_deserialize_from_legacy_bytes(bytes: &[u8]) -> Result<Network> {
+    if let Ok(deserialized) = bincode::deserialize::<Vec<OwnedLayerTuple>>(bytes) {
+        let network = Network::from_layer_tuple_vec(&deserialized);
+
+        if network.is_consistent() {
+            return Ok(network);
+        }
+    }
+
+    let deserialized = bincode::deserialize::<Vec<LegacyOwnedLayerTuple>>(bytes)?;
+    let network = Network::from_legacy_layer_tuple_vec(&deserialized);
+
+    if network.is_consistent() {
+        Ok(network)
+    } else {
+        Err(Error::InconsistentData("legacy network layout".into()))
+    }
+}
+
+#[cfg(test)]
+mod test_rng {
+    use super::*;
+    use rand::RngCore;
+
+    /// Draws of a recorded seed must never change
+    #[test]
+    fn reproducible() {
+        assert_eq!(rng_from_seed(42, RngStream::Shuffling).next_u64(), 0xca785f2bfa2cd62e);
+        assert_ne!(rng_from_seed(42, RngStream::Initialization).next_u64(),
+            rng_from_seed(42, RngStream::Shuffling).next_u64());
+    }
 }
 
 #[cfg(test)]
@@ -88,19 +176,79 @@ mod test_serialization {
     /// Tests w
     #[test]
     fn serialize() {
+        let mut rng = rng_from_seed(0, RngStream::Initialization);
         let geometry = vec![2, 2, 2, 2];
         let mut network = Network::from_geometry(&geometry);
-        algorithm::network_init_random(&mut network);
-        network_serialize_into_file(&network, "network.bin");
+        algorithm::network_init_random(&mut network, &mut rng);
+        network.set_activation(3, algorithm::ActivationFunctionFamily::Sigmoid);
+        network_serialize_into_file(&network, "network.bin").unwrap();
+        assert!(!Path::new("network.bin.tmp").exists());
         let mut network_clone = network_deserialize_from_file("network.bin").unwrap();
         assert!(network_clone == network);
         network_clone.set_w(1, 0, 0, network.w(1, 0, 0) + 1.0f32);
         assert!(network_clone != network);
     }
+
+    /// Bare dumps of layer tuples predate the model format
+    #[test]
+    fn deserialize_legacy() {
+        let mut rng = rng_from_seed(0, RngStream::Initialization);
+        let mut network = Network::from_geometry(&vec![2, 3, 2]);
+        algorithm::network_init_random(&mut network, &mut rng);
+        network.set_activation(2, algorithm::ActivationFunctionFamily::Tanh);
+        let bytes = bincode::serialize(&network.as_layer_tuple_vec()).unwrap();
+        std::fs::write("network_legacy.bin", bytes).unwrap();
+        let network_legacy = network_deserialize_from_file("network_legacy.bin").unwrap();
+        std::fs::remove_file("network_legacy.bin").unwrap();
+
+        assert!(network_legacy == network);
+    }
+
+    /// Dumps of the original layout, `(z, a, w, b)` w/ per-edge weights and
+    /// biases as nested vectors. Hidden layers used ReLU, and the output was
+    /// the last layer's weighed sums.
+    #[test]
+    fn deserialize_baseline() {
+        use rand::Rng;
+        type BaselineLayerTuple = (Vec<f32>, Vec<f32>, Vec<Vec<f32>>, Vec<Vec<f32>>);
+        let geometry = [2usize, 3, 2];
+        let mut rng = rng_from_seed(0, RngStream::Initialization);
+        let mut edges = |n_from: usize, n_to: usize| -> Vec<Vec<f32>> {
+            (0..n_from).map(|_| (0..n_to).map(|_| rng.gen_range(-1.0f32..1.0)).collect()).collect()
+        };
+        let mut layers: Vec<BaselineLayerTuple> = vec![(vec![], vec![0.0; geometry[0]], vec![], vec![])];
+
+        for ilayer in 1..geometry.len() {
+            let (n_from, n_to) = (geometry[ilayer - 1], geometry[ilayer]);
+            layers.push((vec![0.0; n_to], vec![0.0; n_to], edges(n_from, n_to), edges(n_from, n_to)));
+        }
+
+        let input = vec![0.7f32, -0.4];
+        let mut a = input.clone();
+        let mut z = Vec::new();
+
+        for (ilayer, (_, _, w, b)) in layers.iter().enumerate().skip(1) {
+            z = (0..geometry[ilayer])
+                .map(|ito| (0..a.len()).map(|ifrom| a[ifrom] * w[ifrom][ito] + b[ifrom][ito]).sum())
+                .collect();
+            a = z.iter().map(|z: &f32| z.max(0.0)).collect();
+        }
+
+        std::fs::write("network_baseline.bin", bincode::serialize(&layers).unwrap()).unwrap();
+        let mut network = network_deserialize_from_file("network_baseline.bin").unwrap();
+        std::fs::remove_file("network_baseline.bin").unwrap();
+        algorithm::ForwardPropagation.run(&mut network, &input);
+
+        assert!(z.iter().any(|z| *z < 0.0));
+
+        for (output, z) in network.output_layer().iter().zip(&z) {
+            assert!((output - z).abs() < 1e-5, "{} vs {}", output, z);
+        }
+    }
 }
 
 /// Compares float vectors ignoring NaN operations
-pub fn vecf32_float_safe_is_eq(lhs: &Coeff, rhs: &Coeff) -> bool {
+pub fn vecf32_float_safe_is_eq(lhs: &[f32], rhs: &[f32]) -> bool {
     if lhs.len() == rhs.len() {
         lhs.iter().zip(rhs.iter())
             .fold(true, |acc, item| {